@@ -1,7 +1,10 @@
 // Copyright 2020 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use engine::vault::{BoxProvider, DBView, Key, PreparedRead, ReadResult, RecordHint, RecordId, WriteRequest};
+use engine::{
+    snapshot::{password_key, password_salt},
+    vault::{BoxProvider, CollisionPolicy, DBView, Key, PreparedRead, ReadResult, RecordHint, RecordId, WriteRequest},
+};
 
 use std::collections::HashMap;
 
@@ -156,6 +159,50 @@ impl<P: BoxProvider + Send + Sync + Clone + 'static> Bucket<P> {
         buffer
     }
 
+    #[allow(dead_code)]
+    /// Exports the records of a Vault sealed with a key derived from the `password`. The salt of the key is stored
+    /// along with the export. Accepts a `Key<P>` and returns the export as a `Vec<u8>`.
+    pub fn export_vault(&mut self, key: Key<P>, password: &[u8]) -> Vec<u8> {
+        let salt = password_salt().expect(line_error!());
+        let export_key = Key::load(password_key(password, &salt).expect(line_error!())).expect(line_error!());
+        let mut export = vec![];
+
+        self.take(key, |view, reads| {
+            export = view.export(&export_key).expect(line_error!());
+
+            reads
+        });
+
+        bincode::serialize(&(salt, export)).expect(line_error!())
+    }
+
+    #[allow(dead_code)]
+    /// Imports an export made by `export_vault` with the same `password` into the Vault of the given `Key<P>`.
+    /// Records whose id is already in use are handled according to the `CollisionPolicy`. Returns the `RecordId`s of
+    /// the imported Records.
+    pub fn import_vault(
+        &mut self,
+        key: Key<P>,
+        export: &[u8],
+        password: &[u8],
+        policy: CollisionPolicy,
+    ) -> Vec<RecordId> {
+        let (salt, export): (Vec<u8>, Vec<u8>) = bincode::deserialize(export).expect(line_error!());
+        let export_key = Key::load(password_key(password, &salt).expect(line_error!())).expect(line_error!());
+        let mut buffer = vec![];
+
+        self.take(key, |view, mut reads| {
+            let report = view.import(&export, &export_key, policy).expect(line_error!());
+
+            buffer = report.imported.into_iter().map(|(_, id)| id).collect();
+            reads.extend(report.writes.iter().map(write_to_read));
+
+            reads
+        });
+
+        buffer
+    }
+
     /// Repopulates the data in the Bucket given a Vec<u8> of state from a snapshot.  Returns a `Vec<Key<P>,
     /// Vec<Vec<RecordId>>`.
     pub fn repopulate_data(&mut self, state: Vec<u8>) -> (Vec<Key<P>>, Vec<Vec<RecordId>>) {
@@ -280,6 +327,30 @@ mod tests {
         println!("{:?}", std::str::from_utf8(&data));
     }
 
+    #[test]
+    fn test_export_import() {
+        use crate::provider::Provider;
+
+        let key1 = Key::<Provider>::random().expect(line_error!());
+        let key2 = Key::<Provider>::random().expect(line_error!());
+
+        let mut bucket = Bucket::<Provider>::new();
+
+        let (key1, rid1) = bucket.create_and_init_vault(key1);
+        bucket.write_payload(
+            key1.clone(),
+            rid1,
+            b"some data".to_vec(),
+            RecordHint::new(b"").expect(line_error!()),
+        );
+
+        let export = bucket.export_vault(key1, b"password");
+        let ids = bucket.import_vault(key2.clone(), &export, b"password", CollisionPolicy::Fail);
+
+        assert_eq!(ids, vec![rid1]);
+        assert_eq!(bucket.read_data(key2, rid1), b"some data".to_vec());
+    }
+
     fn write_to_read(write: &WriteRequest) -> ReadResult {
         ReadResult::new(write.kind(), write.id(), write.data())
     }
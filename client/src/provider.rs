@@ -8,7 +8,7 @@ use engine::random::{
     OsRng,
 };
 
-use engine::vault::{BoxProvider, Error, Key, Result};
+use engine::vault::{BoxProvider, CipherSuite, Error, Key, Result};
 
 #[derive(Debug, Clone)]
 pub struct Provider;
@@ -27,6 +27,10 @@ impl BoxProvider for Provider {
         Self::NONCE_LEN + Self::TAG_LEN
     }
 
+    fn box_cipher_suite() -> CipherSuite {
+        CipherSuite::XChaCha20Poly1305
+    }
+
     fn box_seal(key: &Key<Self>, ad: &[u8], data: &[u8]) -> Result<Vec<u8>> {
         let mut boxx = vec![0; data.len() + Self::box_overhead()];
         let (nonce, cipher) = boxx.split_at_mut(Self::NONCE_LEN);
@@ -40,5 +40,5 @@ mod serialize;
 pub use error::{Error, Result};
 
 pub use files::{home_dir, snapshot_dir};
-pub use logic::{decrypt_snapshot, encrypt_snapshot, update_snapshot};
+pub use logic::{decrypt_snapshot, encrypt_snapshot, password_key, password_salt, update_snapshot};
 pub use serialize::{deserialize_buffer, serialize_map};
@@ -48,6 +48,19 @@ fn derive_key_from_password(password: &[u8], salt: &pwhash::Salt) -> crate::Resu
     }
 }
 
+/// generate a random salt for `password_key`.
+pub fn password_salt() -> crate::Result<Vec<u8>> {
+    Ok(generate_salt()?.0.to_vec())
+}
+
+/// derive a key from a password and a salt with the snapshot's KDF, e.g. to seal a vault export with a password.
+pub fn password_key(password: &[u8], salt: &[u8]) -> crate::Result<Vec<u8>> {
+    let salt = pwhash::Salt::from_slice(salt).ok_or_else(|| crate::Error::SnapshotError("Invalid salt".into()))?;
+    let Key(key) = derive_key_from_password(password, &salt)?;
+
+    Ok(key.to_vec())
+}
+
 /// create an encryption push stream and a header.
 fn create_stream(&Key(ref key): &Key) -> crate::Result<(Stream<Push>, Header)> {
     let stream_key = secretstream::Key(key.to_owned());
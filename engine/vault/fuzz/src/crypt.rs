@@ -1,7 +1,7 @@
 // Copyright 2020 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use vault::{BoxProvider, Error, Key};
+use vault::{BoxProvider, CipherSuite, Error, Key};
 
 use primitives::{
     cipher::{AeadCipher, Cipher},
@@ -106,6 +106,10 @@ impl BoxProvider for Provider {
         Self::NONCE + Self::TAG
     }
 
+    fn box_cipher_suite() -> CipherSuite {
+        CipherSuite::XChaCha20Poly1305
+    }
+
     fn box_seal(key: &Key<Self>, ad: &[u8], plaintext: &[u8]) -> vault::Result<Vec<u8>> {
         // partition buffer and generate a nonce
         let mut cbox = vec![0; plaintext.len() + Self::box_overhead()];
@@ -3,19 +3,59 @@
 
 use std::{
     convert::TryFrom,
-    fmt::Debug,
+    fmt::{self, Debug, Display, Formatter},
     hash::{Hash, Hasher},
     marker::PhantomData,
 };
 
 use serde::{Deserialize, Serialize};
 
+/// The AEAD construction used by a `BoxProvider`. The suite is recorded in the `InitTransaction` of every chain and
+/// checked when the vault is loaded, so a vault can't be opened by a provider using a different algorithm.
+#[repr(u64)]
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub enum CipherSuite {
+    XChaCha20Poly1305 = 1,
+}
+
+impl CipherSuite {
+    /// get the suite's identifier as it is stored in the transactions.
+    pub fn id(self) -> u64 {
+        self as u64
+    }
+}
+
+impl TryFrom<u64> for CipherSuite {
+    type Error = crate::Error;
+
+    fn try_from(id: u64) -> Result<Self, Self::Error> {
+        match id {
+            1 => Ok(CipherSuite::XChaCha20Poly1305),
+            _ => Err(crate::Error::CipherSuiteError(format!(
+                "{} is not a known cipher suite",
+                id
+            ))),
+        }
+    }
+}
+
+impl Display for CipherSuite {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CipherSuite::XChaCha20Poly1305 => f.write_str("XChaCha20-Poly1305"),
+        }
+    }
+}
+
 /// A provider interface between the vault and a crypto box. See libsodium's [secretbox](https://libsodium.gitbook.io/doc/secret-key_cryptography/secretbox) for an example.
 pub trait BoxProvider: Sized {
     /// function for the key length of the crypto box
     fn box_key_len() -> usize;
     /// gets the crypto box's overhead
     fn box_overhead() -> usize;
+    /// gets the cipher suite the crypto box seals data with. The vault doesn't implement any cipher itself, it records
+    /// the suite declared here and refuses to load data recorded with a different one.
+    fn box_cipher_suite() -> CipherSuite;
 
     /// seals some data into the crypto box using the `key` and the `ad`
     fn box_seal(key: &Key<Self>, ad: &[u8], data: &[u8]) -> crate::Result<Vec<u8>>;
@@ -113,7 +153,7 @@ impl<T: BoxProvider> Hash for Key<T> {
 }
 
 impl<T: BoxProvider> Debug for Key<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "KeyData")
     }
 }
@@ -37,10 +37,13 @@ mod vault;
 
 pub use crate::{
     base64::{Base64Decodable, Base64Encodable},
-    crypto_box::{BoxProvider, Decrypt, Encrypt, Key},
-    types::utils::{ChainId, RecordHint},
+    crypto_box::{BoxProvider, CipherSuite, Decrypt, Encrypt, Key},
+    types::utils::{ChainId, Purpose, RecordHint, RecordLabel, RecordMetadata, RecordPolicy},
     vault::{
-        DBReader, DBView, DBWriter, DeleteRequest, Kind, PreparedRead, ReadRequest, ReadResult, RecordId, WriteRequest,
+        BlobReader, BlobWriter, CollisionPolicy, Conflict, ConflictResolver, DBReader, DBView, DBWriter, DeleteRequest,
+        Event, GcProgress, Hooks, ImportReport, Issue, KeepBoth, Kind, LatestWins, Merge, MergeReport, PreparedRead,
+        Quota, ReadCache, ReadCacheStats, ReadRequest, ReadResult, RecordId, RecordPage, RecordVersion, RekeyProgress,
+        Resolution, SharedView, Usage, VerifyReport, WriteRequest, CHUNK_SIZE,
     },
 };
 
@@ -63,10 +66,16 @@ pub enum Error {
     OtherError(String),
     #[error("Crypto Error: `{0}`")]
     CryptoError(String),
+    #[error("Cipher Suite Error: `{0}`")]
+    CipherSuiteError(String),
     #[error("Value Error: `{0}`")]
     ValueError(String),
     #[error("Protocol Error: `{0}`")]
     ProtocolError(String),
+    #[error("Quota Error: `{0}`")]
+    QuotaError(String),
+    #[error("Policy Error: `{0}`")]
+    PolicyError(String),
 }
 
 // Crate result type
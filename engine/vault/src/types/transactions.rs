@@ -5,9 +5,9 @@
                                    // These fns don't return Self, instead return Transaction struct which let us write this code in a simpler way
 
 use crate::{
-    crypto_box::{Decrypt, Encrypt},
+    crypto_box::{CipherSuite, Decrypt, Encrypt},
     types::{
-        utils::{BlobId, ChainId, RecordHint, TransactionId, Val},
+        utils::{BlobId, ChainId, RecordHint, RecordLabel, RecordMetadata, RecordPolicy, TransactionId, Val},
         AsView, AsViewMut,
     },
 };
@@ -116,6 +116,24 @@ pub struct DataTransaction {
 
     /// a record hint
     pub record_hint: RecordHint,
+
+    /// seconds since the unix epoch when the record was first written
+    pub created: Val,
+
+    /// seconds since the unix epoch when the record was last written
+    pub modified: Val,
+
+    /// a record label
+    pub label: RecordLabel,
+
+    /// seconds since the unix epoch after which the record is treated as revoked, zero if it doesn't expire
+    pub expires: Val,
+
+    /// the number of previous versions of the record to retain
+    pub history: Val,
+
+    /// the purposes the record may not be used for, zero if it is unrestricted
+    pub policy: Val,
 }
 
 /// a typed transaction
@@ -158,11 +176,21 @@ pub struct InitTransaction {
 
     /// counter value
     pub ctr: Val,
+
+    /// the cipher suite used to seal the chain, zero for chains written before suites were recorded
+    pub suite: Val,
 }
 
 impl DataTransaction {
     /// create a new data transaction.
-    pub fn new(chain: ChainId, ctr: Val, id: TransactionId, blob: BlobId, record_hint: RecordHint) -> Transaction {
+    pub fn new(
+        chain: ChainId,
+        ctr: Val,
+        id: TransactionId,
+        blob: BlobId,
+        record_hint: RecordHint,
+        metadata: &RecordMetadata,
+    ) -> Transaction {
         let mut transaction = Transaction::default();
         let view: &mut Self = transaction.view_mut();
 
@@ -172,8 +200,32 @@ impl DataTransaction {
         view.id = id;
         view.blob = blob;
         view.record_hint = record_hint;
+        view.created = metadata.created.into();
+        view.modified = metadata.modified.into();
+        view.label = metadata.label;
+        view.expires = metadata.expires.unwrap_or(0).into();
+        view.history = metadata.history.into();
+        view.policy = metadata.policy.bits().into();
         transaction
     }
+
+    /// get the metadata of the record.
+    pub fn metadata(&self) -> RecordMetadata {
+        RecordMetadata {
+            created: self.created.u64(),
+            modified: self.modified.u64(),
+            label: self.label,
+            expires: Some(self.expires.u64()).filter(|e| *e != 0),
+            history: self.history.u64(),
+            policy: RecordPolicy::from(self.policy.u64()),
+        }
+    }
+
+    /// check if the record has expired at `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        let expires = self.expires.u64();
+        expires != 0 && expires <= now
+    }
 }
 
 impl TypedTransaction for DataTransaction {
@@ -219,7 +271,7 @@ impl Transaction {
 
 impl InitTransaction {
     /// create a new init transaction.
-    pub fn new(chain: ChainId, id: TransactionId, ctr: Val) -> Transaction {
+    pub fn new(chain: ChainId, id: TransactionId, ctr: Val, suite: CipherSuite) -> Transaction {
         let mut transaction = Transaction::default();
         let view: &mut Self = transaction.view_mut();
 
@@ -227,8 +279,17 @@ impl InitTransaction {
         view.id = id;
         view.chain = chain;
         view.ctr = ctr;
+        view.suite = suite.id().into();
         transaction
     }
+
+    /// get the cipher suite recorded for the chain. Returns `None` for chains that predate recorded suites.
+    pub fn suite(&self) -> crate::Result<Option<CipherSuite>> {
+        match self.suite.u64() {
+            0 => Ok(None),
+            id => CipherSuite::try_from(id).map(Some),
+        }
+    }
 }
 
 impl TypedTransaction for InitTransaction {
@@ -237,7 +298,9 @@ impl TypedTransaction for InitTransaction {
     }
 }
 
-const TRANSACTION_MAX_BYTES: usize = 112;
+const TRANSACTION_MAX_BYTES: usize = 176;
+/// transactions written by earlier versions are shorter, the missing fields are read as zero
+const TRANSACTION_MIN_BYTES: usize = 112;
 
 impl Default for Transaction {
     fn default() -> Self {
@@ -246,9 +309,13 @@ impl Default for Transaction {
 }
 impl TryFrom<Vec<u8>> for Transaction {
     type Error = ();
-    fn try_from(vec: Vec<u8>) -> Result<Self, Self::Error> {
+    fn try_from(mut vec: Vec<u8>) -> Result<Self, Self::Error> {
         match vec.len() {
             TRANSACTION_MAX_BYTES => Ok(Self(vec)),
+            TRANSACTION_MIN_BYTES..=TRANSACTION_MAX_BYTES => {
+                vec.resize(TRANSACTION_MAX_BYTES, 0);
+                Ok(Self(vec))
+            }
             _ => Err(()),
         }
     }
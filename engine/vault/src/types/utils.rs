@@ -49,6 +49,150 @@ impl From<[u8; 24]> for RecordHint {
     }
 }
 
+/// a record label
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecordLabel([u8; 24]);
+
+impl RecordLabel {
+    /// create a new label, the label may be up to 24 bytes long
+    pub fn new(label: impl AsRef<[u8]>) -> crate::Result<Self> {
+        let label = match label.as_ref() {
+            label if label.len() <= 24 => label,
+            _ => return Err(crate::Error::InterfaceError),
+        };
+
+        // copy label
+        let mut buf = [0; 24];
+        buf[..label.len()].copy_from_slice(label);
+        Ok(Self(buf))
+    }
+
+    /// check if the label is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|b| *b == 0)
+    }
+}
+
+impl AsRef<[u8]> for RecordLabel {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Debug for RecordLabel {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.base64())
+    }
+}
+
+impl From<[u8; 24]> for RecordLabel {
+    fn from(bs: [u8; 24]) -> Self {
+        Self(bs)
+    }
+}
+
+/// the metadata stored alongside a record's data
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecordMetadata {
+    /// seconds since the unix epoch when the record was first written, zero if unknown
+    pub created: u64,
+    /// seconds since the unix epoch when the record was last written, zero if unknown
+    pub modified: u64,
+    /// a user defined label
+    pub label: RecordLabel,
+    /// seconds since the unix epoch after which the record is treated as revoked
+    pub expires: Option<u64>,
+    /// number of previous versions of the record which are retained
+    pub history: u64,
+    /// the purposes the record may not be used for
+    pub policy: RecordPolicy,
+}
+
+/// a use of a record's data
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Purpose {
+    /// reading the plain data
+    Read = 0,
+    /// exporting the record from the vault
+    Export = 1,
+    /// signing with the record as the key
+    Sign = 2,
+    /// deriving keys from the record
+    Derive = 3,
+    /// encrypting or decrypting with the record as the key
+    Encrypt = 4,
+}
+
+impl Purpose {
+    /// every purpose
+    pub const ALL: [Purpose; 5] = [
+        Purpose::Read,
+        Purpose::Export,
+        Purpose::Sign,
+        Purpose::Derive,
+        Purpose::Encrypt,
+    ];
+
+    fn bit(self) -> u64 {
+        1 << self as u64
+    }
+}
+
+/// the purposes a record may not be used for, the default policy allows every purpose
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecordPolicy(u64);
+
+impl RecordPolicy {
+    /// create a policy which only allows the given purposes
+    pub fn only(purposes: &[Purpose]) -> Self {
+        Purpose::ALL
+            .iter()
+            .filter(|p| !purposes.contains(p))
+            .fold(Self::default(), |policy, p| policy.deny(*p))
+    }
+
+    /// deny a purpose
+    pub fn deny(self, purpose: Purpose) -> Self {
+        Self(self.0 | purpose.bit())
+    }
+
+    /// deny the purposes denied by either policy
+    pub fn restrict(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// check if the policy allows a purpose
+    pub fn allows(self, purpose: Purpose) -> bool {
+        self.0 & purpose.bit() == 0
+    }
+
+    /// check if the policy allows every purpose
+    pub fn is_unrestricted(self) -> bool {
+        self.0 == 0
+    }
+
+    /// get the bits of the denied purposes, unknown bits are kept so newer policies are preserved
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for RecordPolicy {
+    fn from(bits: u64) -> Self {
+        Self(bits)
+    }
+}
+
+impl Debug for RecordPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_set()
+            .entries(Purpose::ALL.iter().filter(|p| !self.allows(**p)))
+            .finish()
+    }
+}
+
 /// a big endian encoded number
 #[repr(transparent)]
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
@@ -3,27 +3,47 @@
 
 use crate::{
     base64::Base64Encodable,
-    crypto_box::{BoxProvider, Decrypt, Encrypt, Key},
+    crypto_box::{BoxProvider, CipherSuite, Decrypt, Encrypt, Key},
     types::{
         transactions::{
             DataTransaction, InitTransaction, RevocationTransaction, SealedBlob, SealedTransaction, Transaction,
         },
-        utils::{BlobId, ChainId, RecordHint, TransactionId, Val},
+        utils::{BlobId, ChainId, Purpose, RecordHint, RecordLabel, RecordMetadata, RecordPolicy, TransactionId, Val},
     },
 };
 
 use serde::{Deserialize, Serialize};
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     convert::{TryFrom, TryInto},
     fmt::{self, Debug, Display, Formatter},
+    ops::Bound,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 mod chain;
+mod export;
+mod hooks;
+mod index;
+mod merge;
 mod protocol;
+mod read_cache;
+mod shared;
+mod stream;
+mod verify;
 
-pub use crate::vault::protocol::{DeleteRequest, Kind, ReadRequest, ReadResult, WriteRequest};
+pub use crate::vault::{
+    export::{CollisionPolicy, ImportReport},
+    hooks::{Event, Hooks},
+    merge::{Conflict, ConflictResolver, KeepBoth, LatestWins, Merge, MergeReport, Resolution},
+    protocol::{DeleteRequest, Kind, ReadRequest, ReadResult, WriteRequest},
+    read_cache::{ReadCache, ReadCacheStats},
+    shared::SharedView,
+    stream::{BlobReader, BlobWriter, CHUNK_SIZE},
+    verify::{Issue, VerifyReport},
+};
 
 /// A record identifier
 #[repr(transparent)]
@@ -68,30 +88,184 @@ impl RecordId {
 pub struct DBView<P: BoxProvider> {
     key: Key<P>,
     txs: HashMap<TransactionId, Transaction>,
-    chains: HashMap<ChainId, chain::Chain>,
+    chains: BTreeMap<ChainId, chain::Chain>,
     blobs: HashMap<BlobId, Vec<TransactionId>>,
+    index: index::Index,
+    usage: Usage,
+    pending: Mutex<Pending>,
+    quota: Quota,
+    hooks: Hooks,
+    read_cache: Option<ReadCache>,
+    clock: Option<Clock>,
     cache: HashMap<BlobId, SealedBlob>,
+    rotation: Option<Rotation<P>>,
+    suite: Option<CipherSuite>,
+}
+
+/// The state of a key rotation: the key the vault is being rotated away from and the entries still sealed with it.
+/// Blobs which weren't loaded may be sealed with either key, so they are tracked as `unloaded`.
+struct Rotation<P: BoxProvider> {
+    previous: Key<P>,
+    txs: BTreeSet<TransactionId>,
+    blobs: BTreeSet<BlobId>,
+    unloaded: BTreeSet<BlobId>,
+}
+
+/// A source of the current time in seconds since the unix epoch.
+type Clock = Arc<dyn Fn() -> u64 + Send + Sync>;
+
+/// The records and encrypted bytes added by the writes a view prepared. They count against the quota until the view is
+/// reloaded with the applied writes.
+#[derive(Default)]
+struct Pending {
+    records: BTreeSet<ChainId>,
+    bytes: usize,
+}
+
+/// A version of a record.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RecordVersion {
+    /// the counter of the version's transaction
+    pub ctr: u64,
+    /// the record hint of the version
+    pub hint: RecordHint,
+    /// the metadata of the version
+    pub metadata: RecordMetadata,
+}
+
+/// A page of records returned by `DBView::list`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecordPage {
+    /// the records on this page, ordered by their id
+    pub records: Vec<(RecordId, RecordHint)>,
+    /// the cursor to pass to `DBView::list` to get the next page, `None` if this is the last page
+    pub next: Option<RecordId>,
+}
+
+/// The storage used by a vault.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// number of valid records when the vault was loaded
+    pub records: usize,
+    /// number of transactions, including the ones awaiting garbage collection
+    pub transactions: usize,
+    /// number of loaded blobs
+    pub blobs: usize,
+    /// size of the loaded transactions and of the blobs referred to by valid records, in their encrypted form
+    pub bytes: usize,
+}
+
+/// The limits enforced on a vault, `None` means unlimited.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Quota {
+    /// maximum number of valid records
+    pub max_records: Option<usize>,
+    /// maximum number of encrypted bytes
+    pub max_bytes: Option<usize>,
+}
+
+/// The progress of an incremental garbage collection.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GcProgress {
+    /// number of transactions collected by this step
+    pub collected: usize,
+    /// number of transactions left to collect after this step
+    pub remaining: usize,
+}
+
+impl GcProgress {
+    /// Check if the garbage collection is complete once this step is applied.
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+/// The progress of a key rotation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RekeyProgress {
+    /// number of loaded entries still sealed with the previous key, along with the blobs which weren't loaded
+    pub remaining: usize,
+    /// number of loaded entries, along with the blobs which weren't loaded
+    pub total: usize,
+}
+
+impl RekeyProgress {
+    /// Check if every loaded entry is sealed with the current key.
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
 }
 
 impl<P: BoxProvider> DBView<P> {
     /// Opens a vault using a key. Accepts the `ReadResult`:s of the vault transactions you want to load.
     pub fn load<R: AsRef<ReadResult>>(key: Key<P>, reads: impl Iterator<Item = R>) -> crate::Result<Self> {
+        Self::load_with(key, None, reads)
+    }
+
+    /// Opens a vault which is being rotated from the `previous` key to `key`. Entries may be sealed with either key;
+    /// the ones still sealed with the `previous` key are re-encrypted in batches by `rekey`. Since the rotation state
+    /// is derived from the loaded entries, an interrupted rotation is resumed by loading the vault this way again.
+    pub fn load_rotating<R: AsRef<ReadResult>>(
+        key: Key<P>,
+        previous: Key<P>,
+        reads: impl Iterator<Item = R>,
+    ) -> crate::Result<Self> {
+        Self::load_with(key, Some(previous), reads)
+    }
+
+    fn load_with<R: AsRef<ReadResult>>(
+        key: Key<P>,
+        previous: Option<Key<P>>,
+        reads: impl Iterator<Item = R>,
+    ) -> crate::Result<Self> {
         let mut txs = HashMap::new();
         let mut raw_chains: HashMap<_, Vec<TransactionId>> = HashMap::new();
         let mut cache = HashMap::new();
         let mut blobs: HashMap<_, Vec<_>> = HashMap::new();
+        let mut rotation = previous.map(|previous| Rotation {
+            previous,
+            txs: BTreeSet::new(),
+            blobs: BTreeSet::new(),
+            unloaded: BTreeSet::new(),
+        });
 
+        let mut bytes = 0;
+        let mut recorded = None;
         for r in reads {
             let r = r.as_ref();
             match r.kind() {
                 Kind::Transaction => {
+                    bytes += r.data().len();
                     let id = TransactionId::try_from(r.id())?;
-                    let tx = SealedTransaction::from(r.data()).decrypt(&key, r.id())?;
+                    let sealed = SealedTransaction::from(r.data());
+                    let tx = match (sealed.decrypt(&key, r.id()), &mut rotation) {
+                        (Ok(tx), _) => tx,
+                        (Err(_), Some(rot)) => {
+                            let tx = sealed.decrypt(&rot.previous, r.id())?;
+                            rot.txs.insert(id);
+                            tx
+                        }
+                        (Err(e), None) => return Err(e),
+                    };
                     if id != tx.untyped().id {
                         // TODO: more precise error w/ the failing transaction id
                         return Err(crate::Error::InterfaceError);
                     }
 
+                    if let Some(itx) = tx.typed::<InitTransaction>() {
+                        match itx.suite()? {
+                            Some(suite) if suite != P::box_cipher_suite() => {
+                                return Err(crate::Error::CipherSuiteError(format!(
+                                    "vault was sealed with {} but the provider uses {}",
+                                    suite,
+                                    P::box_cipher_suite()
+                                )));
+                            }
+                            Some(suite) => recorded = Some(suite),
+                            None => (),
+                        }
+                    }
+
                     if let Some(dtx) = tx.typed::<DataTransaction>() {
                         blobs.entry(dtx.blob).or_default().push(id);
                     }
@@ -101,37 +275,349 @@ impl<P: BoxProvider> DBView<P> {
                 }
                 Kind::Blob => {
                     let id = BlobId::try_from(r.id())?;
-                    cache.insert(id, SealedBlob::from(r.data()));
+                    let sb = SealedBlob::from(r.data());
+                    if let Some(rot) = &mut rotation {
+                        if sb.decrypt(&key, id).is_err() {
+                            sb.decrypt(&rot.previous, id)?;
+                            rot.blobs.insert(id);
+                        }
+                    }
+                    cache.insert(id, sb);
                     blobs.entry(id).or_default();
                 }
             }
         }
 
-        let mut chains = HashMap::new();
+        let now = timestamp();
+        let mut chains = BTreeMap::new();
         for (cid, chain) in raw_chains.iter_mut() {
-            chains.insert(*cid, chain::Chain::prune(chain.iter().filter_map(|t| txs.get(t)))?);
+            chains.insert(*cid, chain::Chain::prune(chain.iter().filter_map(|t| txs.get(t)), now)?);
+        }
+        let mut index = index::Index::default();
+        let mut records = 0;
+        for tx in chains
+            .values()
+            .filter_map(|c| c.data())
+            .filter_map(|tx_id| txs.get(&tx_id))
+            .filter_map(|tx| tx.typed::<DataTransaction>())
+            .filter(|tx| !stream::is_chunk(&tx.label))
+        {
+            index.insert(RecordId(tx.chain), tx.record_hint, tx.label);
+            records += 1;
+        }
+        // NB blobs no valid version refers to are never read again, so they don't take up any of the quota
+        let live = live_blobs(&chains, &txs);
+        bytes += cache
+            .iter()
+            .filter(|(id, _)| live.contains(*id))
+            .map(|(_, blob)| blob.as_ref().len())
+            .sum::<usize>();
+        if let Some(rot) = &mut rotation {
+            rot.unloaded = live.iter().filter(|id| !cache.contains_key(*id)).copied().collect();
         }
 
+        let usage = Usage {
+            records,
+            transactions: txs.len(),
+            blobs: cache.len(),
+            bytes,
+        };
+
         Ok(Self {
             key,
             txs,
             chains,
             blobs,
+            index,
             cache,
+            usage,
+            pending: Mutex::default(),
+            quota: Quota::default(),
+            hooks: Hooks::default(),
+            read_cache: None,
+            clock: None,
+            rotation,
+            suite: recorded,
         })
     }
 
+    /// Get the progress of the key rotation. A vault which wasn't loaded with `load_rotating` has nothing to rotate.
+    pub fn rekey_progress(&self) -> RekeyProgress {
+        let unloaded = self.rotation.as_ref().map(|rot| rot.unloaded.len()).unwrap_or(0);
+        let total = self.txs.len() + self.cache.len() + unloaded;
+        let remaining = self
+            .rotation
+            .as_ref()
+            .map(|rot| rot.txs.len() + rot.blobs.len() + unloaded)
+            .unwrap_or(0);
+        RekeyProgress { remaining, total }
+    }
+
+    /// Re-encrypt at most `limit` of the entries still sealed with the previous key. The returned `WriteRequest`s
+    /// replace the entries under their existing ids, so applying them and reloading the vault advances the rotation.
+    /// Fails with `Error::ProtocolError` unless the blobs of every current and retained version were loaded, since
+    /// their key can't be told otherwise.
+    pub fn rekey(&self, limit: usize) -> crate::Result<Vec<WriteRequest>> {
+        let rot = match &self.rotation {
+            Some(rot) => rot,
+            None => return Ok(vec![]),
+        };
+        if !rot.unloaded.is_empty() {
+            return Err(crate::Error::ProtocolError(format!(
+                "{} blobs weren't loaded, load every blob to rotate the key",
+                rot.unloaded.len()
+            )));
+        }
+
+        let mut writes = Vec::new();
+        for id in rot.txs.iter().take(limit) {
+            // NB the transactions are kept decrypted, so they only need to be sealed again
+            let tx = self.txs.get(id).unwrap();
+            writes.push(WriteRequest::transaction(id, &tx.encrypt(&self.key, id)?));
+        }
+
+        for id in rot.blobs.iter().take(limit - writes.len()) {
+            let data = self.cache.get(id).unwrap().decrypt(&rot.previous, id)?;
+            writes.push(WriteRequest::blob(id, &data.encrypt(&self.key, id)?));
+        }
+
+        Ok(writes)
+    }
+
+    /// Get the key to open the blob with `id`.
+    fn blob_key(&self, id: &BlobId) -> &Key<P> {
+        match &self.rotation {
+            Some(rot) if rot.blobs.contains(id) => &rot.previous,
+            _ => &self.key,
+        }
+    }
+
+    /// Get the cipher suite recorded by the vault's chains, `None` if the vault is empty or was only written by
+    /// versions which didn't record the suite.
+    pub fn cipher_suite(&self) -> Option<CipherSuite> {
+        self.suite
+    }
+
     /// Creates an iterator over all valid record identifiers and their corresponding record hints
     pub fn records<'a>(&'a self) -> impl Iterator<Item = (RecordId, RecordHint)> + 'a {
-        self.chains.values().filter_map(move |r| {
-            r.data()
-                .as_ref()
-                .and_then(|tx_id| self.txs.get(tx_id))
-                .and_then(|tx| tx.typed::<DataTransaction>())
-                .map(|tx| (RecordId(tx.chain), tx.record_hint))
+        self.chains
+            .keys()
+            .filter_map(move |cid| self.record_tx(cid).map(|tx| (RecordId(tx.chain), tx.record_hint)))
+    }
+
+    /// Creates an iterator over all valid record identifiers, their record hints and their metadata
+    pub fn records_with_metadata<'a>(&'a self) -> impl Iterator<Item = (RecordId, RecordHint, RecordMetadata)> + 'a {
+        self.entries_with_metadata()
+            .filter(|(_, _, metadata)| !stream::is_chunk(&metadata.label))
+    }
+
+    /// Like `records_with_metadata`, but including the chunks of large blobs.
+    fn entries_with_metadata<'a>(&'a self) -> impl Iterator<Item = (RecordId, RecordHint, RecordMetadata)> + 'a {
+        self.chains.keys().filter_map(move |cid| {
+            self.data_tx(cid)
+                .map(|tx| (RecordId(tx.chain), tx.record_hint, tx.metadata()))
         })
     }
 
+    /// Get the metadata of a record. Returns `None` if the record doesn't exist or is empty.
+    pub fn metadata(&self, record: &RecordId) -> Option<RecordMetadata> {
+        self.record_tx(&record.0).map(|tx| tx.metadata())
+    }
+
+    /// Get the current data transaction of a chain. Records which expired after the vault was loaded are treated as
+    /// revoked, like the ones which expired before.
+    fn data_tx(&self, chain: &ChainId) -> Option<&DataTransaction> {
+        let now = self.now();
+        self.chains
+            .get(chain)
+            .and_then(|c| c.data())
+            .and_then(|tx_id| self.txs.get(&tx_id))
+            .and_then(|tx| tx.typed::<DataTransaction>())
+            .filter(|tx| !tx.is_expired(now))
+    }
+
+    /// Get the current data transaction of a record, the chunks of large blobs are hidden.
+    fn record_tx(&self, chain: &ChainId) -> Option<&DataTransaction> {
+        self.data_tx(chain).filter(|tx| !stream::is_chunk(&tx.label))
+    }
+
+    /// Keep the records which are still valid.
+    fn valid<'a>(&'a self, ids: impl Iterator<Item = RecordId> + 'a) -> impl Iterator<Item = RecordId> + 'a {
+        ids.filter(move |id| self.data_tx(&id.0).is_some())
+    }
+
+    /// Get the retained previous versions of a record, newest first. The versions are identified by their counter.
+    pub fn history(&self, record: &RecordId) -> Vec<RecordVersion> {
+        self.chains
+            .get(&record.0)
+            .map(|c| {
+                c.history()
+                    .iter()
+                    .filter_map(|tx_id| self.txs.get(tx_id))
+                    .filter_map(|tx| tx.typed::<DataTransaction>())
+                    .map(|tx| RecordVersion {
+                        ctr: tx.ctr.u64(),
+                        hint: tx.record_hint,
+                        metadata: tx.metadata(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the policy of a version of a record, which is restricted by the record's current policy.
+    fn version_policy(&self, tx: &DataTransaction) -> RecordPolicy {
+        let current = self.data_tx(&tx.chain).map(|c| c.metadata().policy).unwrap_or_default();
+        tx.metadata().policy.restrict(current)
+    }
+
+    /// Get the current or a retained data transaction of a chain by its counter.
+    fn version_tx(&self, chain: &ChainId, version: u64) -> Option<&DataTransaction> {
+        let c = self.chains.get(chain)?;
+        c.data()
+            .iter()
+            .chain(c.history().iter())
+            .filter_map(|tx_id| self.txs.get(tx_id))
+            .filter_map(|tx| tx.typed::<DataTransaction>())
+            .find(|tx| tx.ctr.u64() == version)
+    }
+
+    /// Get the data of a record whose blob has been loaded. The record's policy isn't checked.
+    fn loaded_data(&self, id: &RecordId) -> crate::Result<Vec<u8>> {
+        match self
+            .data_tx(&id.0)
+            .map(|tx| self.reader().prepare_blob(tx.blob))
+            .transpose()?
+        {
+            Some(PreparedRead::CacheHit(data)) => Ok(data),
+            _ => Err(crate::Error::ProtocolError(format!(
+                "the data of record {} isn't loaded",
+                id
+            ))),
+        }
+    }
+
+    /// Find the records whose hint equals `hint`, ordered by their id. The lookup uses the in-memory index built at
+    /// load, record data isn't decrypted.
+    pub fn find_by_hint<'a>(&'a self, hint: &'a RecordHint) -> impl Iterator<Item = RecordId> + 'a {
+        self.valid(self.index.hint(hint))
+    }
+
+    /// Find the records whose hint starts with `prefix`, ordered by their hint. Since hints are zero padded, a prefix
+    /// with trailing zeros matches shorter hints as well.
+    pub fn find_by_hint_prefix<'a>(&'a self, prefix: &'a [u8]) -> impl Iterator<Item = RecordId> + 'a {
+        self.valid(self.index.hint_prefix(prefix))
+    }
+
+    /// Find the records labeled `label`, ordered by their id. Records without a label can't be found this way.
+    pub fn find_by_label<'a>(&'a self, label: &'a RecordLabel) -> impl Iterator<Item = RecordId> + 'a {
+        self.valid(self.index.label(label))
+    }
+
+    /// Get the number of valid records.
+    pub fn record_count(&self) -> usize {
+        self.records().count()
+    }
+
+    /// Get the number of records, entries and encrypted bytes loaded into the vault.
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// Limit the size of the vault. Writes that would exceed the quota fail with `Error::QuotaError`. The writes
+    /// prepared by a view count against the quota until it is reloaded, whether or not they were applied. Revocations
+    /// are always allowed so space can be reclaimed.
+    pub fn with_quota(mut self, quota: Quota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// Emit the events of this view to the subscribers of `hooks`.
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Keep the data of recently read records in `cache`, so repeated reads don't decrypt the data again. Cached data
+    /// no valid record of this view refers to is dropped.
+    pub fn with_read_cache(mut self, cache: ReadCache) -> Self {
+        cache.retain(&live_blobs(&self.chains, &self.txs));
+        self.read_cache = Some(cache);
+        self
+    }
+
+    /// Take the current time from `clock` instead of the system time, in seconds since the unix epoch. The clock
+    /// decides which records have expired since the vault was loaded and timestamps the written data, loading always
+    /// uses the system time.
+    pub fn with_clock(mut self, clock: impl Fn() -> u64 + Send + Sync + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Get the current time in seconds since the unix epoch.
+    fn now(&self) -> u64 {
+        self.clock.as_ref().map(|c| c()).unwrap_or_else(timestamp)
+    }
+
+    /// Count writes to the `records` adding `bytes` encrypted bytes against the quota. Records which are valid or
+    /// already written by a pending write don't count again.
+    fn reserve(&self, records: impl IntoIterator<Item = ChainId>, bytes: usize) -> crate::Result<()> {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        let new: BTreeSet<_> = records
+            .into_iter()
+            .filter(|c| self.data_tx(c).is_none() && !pending.records.contains(c))
+            .collect();
+
+        if let Some(max) = self.quota.max_records {
+            if self.usage.records + pending.records.len() + new.len() > max {
+                return Err(crate::Error::QuotaError(format!(
+                    "the vault is limited to {} records",
+                    max
+                )));
+            }
+        }
+
+        if let Some(max) = self.quota.max_bytes {
+            if self.usage.bytes + pending.bytes + bytes > max {
+                return Err(crate::Error::QuotaError(format!(
+                    "the vault is limited to {} bytes",
+                    max
+                )));
+            }
+        }
+
+        pending.records.extend(new);
+        pending.bytes += bytes;
+        Ok(())
+    }
+
+    /// List at most `limit` valid records ordered by their id, starting after the record `after`. Pass the `next`
+    /// cursor of the returned page to get the following page. The ordering is stable across reloads. A `limit` of zero
+    /// is treated as one, so a page is only empty if there are no more records.
+    pub fn list(&self, after: Option<&RecordId>, limit: usize) -> RecordPage {
+        let limit = limit.max(1);
+        let start = match after {
+            Some(id) => Bound::Excluded(id.0),
+            None => Bound::Unbounded,
+        };
+
+        let mut records = self
+            .chains
+            .range((start, Bound::Unbounded))
+            .filter_map(|(cid, _)| self.record_tx(cid).map(|tx| (RecordId(*cid), tx.record_hint)))
+            .take(limit + 1)
+            .collect::<Vec<_>>();
+
+        let next = if records.len() > limit {
+            records.truncate(limit);
+            records.last().map(|(id, _)| *id)
+        } else {
+            None
+        };
+
+        RecordPage { records, next }
+    }
+
     /// Creates an iterator over all valid records ids.
     pub fn all<'a>(&'a self) -> impl ExactSizeIterator<Item = RecordId> + 'a {
         self.chains.keys().map(|k| RecordId(*k))
@@ -191,17 +677,52 @@ impl<P: BoxProvider> DBView<P> {
             view: self,
             chain: record.0,
             next_ctr,
+            label: None,
+            created: None,
+            expires: None,
+            history: None,
+            policy: RecordPolicy::default(),
+            metered: true,
+            silent: false,
         }
     }
 
     /// Garbage collect the records.
     pub fn gc(&self) -> Vec<DeleteRequest> {
         // TODO: iterate through the blobs and check if any can be removed
-        self.chains
+        let deletes: Vec<_> = self
+            .chains
             .values()
             .map(|r| r.garbage().iter().cloned().map(DeleteRequest::transaction))
             .flatten()
-            .collect()
+            .collect();
+
+        self.hooks.emit(Event::Collected {
+            transactions: deletes.len(),
+        });
+        deletes
+    }
+
+    /// Garbage collect at most `limit` transactions. Collection proceeds in a stable order, so applying the returned
+    /// `DeleteRequest`s, reloading the vault and calling this again continues where the last step left off.
+    pub fn gc_step(&self, limit: usize) -> (Vec<DeleteRequest>, GcProgress) {
+        let total: usize = self.chains.values().map(|c| c.garbage().len()).sum();
+        let deletes: Vec<_> = self
+            .chains
+            .values()
+            .flat_map(|c| c.garbage().iter().cloned())
+            .take(limit)
+            .map(DeleteRequest::transaction)
+            .collect();
+
+        let progress = GcProgress {
+            collected: deletes.len(),
+            remaining: total - deletes.len(),
+        };
+        self.hooks.emit(Event::Collected {
+            transactions: deletes.len(),
+        });
+        (deletes, progress)
     }
 }
 
@@ -253,6 +774,12 @@ impl<'a, P: BoxProvider> DBReader<'a, P> {
     /// Prepare a record for reading. Create a `ReadRequest` to read the record with inputted `id`. Returns `None` if
     /// there was no record for that ID
     pub fn prepare_read(&self, record: &RecordId) -> crate::Result<PreparedRead> {
+        self.prepare_use(record, Purpose::Read)
+    }
+
+    /// Prepare a record for the given `purpose`, e.g. for signing. Fails with `Error::PolicyError` if the record's
+    /// policy denies the purpose.
+    pub fn prepare_use(&self, record: &RecordId, purpose: Purpose) -> crate::Result<PreparedRead> {
         match self.view.chains.get(&record.0).map(|r| (r.init(), r.data())) {
             None | Some((None, _)) => Ok(PreparedRead::NoSuchRecord),
             Some((_, None)) => Ok(PreparedRead::RecordIsEmpty),
@@ -260,44 +787,134 @@ impl<'a, P: BoxProvider> DBReader<'a, P> {
                 // TODO: if we use references/boxes instead of ids then these never-failing lookups
                 // can be removed
                 let tx = self.view.txs.get(&tx_id).unwrap().typed::<DataTransaction>().unwrap();
-                match self.view.cache.get(&tx.blob) {
-                    Some(sb) => Ok(PreparedRead::CacheHit(sb.decrypt(&self.view.key, tx.blob)?)),
-                    None => Ok(PreparedRead::CacheMiss(ReadRequest::blob(tx.blob))),
+                if tx.is_expired(self.view.now()) {
+                    // NB the record expired after the vault was loaded
+                    return Ok(PreparedRead::NoSuchRecord);
                 }
+
+                check_policy(record, tx.metadata().policy, purpose)?;
+                self.view.hooks.emit(Event::Read {
+                    record: *record,
+                    purpose,
+                });
+                self.prepare_blob(tx.blob)
             }
         }
     }
 
-    /// Open a record given a `ReadResult`.  Returns a vector of bytes.
+    /// Prepare a version of a record for reading, as listed by `DBView::history`. The current version may be read
+    /// this way too. Both the version's and the current policy have to allow reading.
+    pub fn prepare_read_version(&self, record: &RecordId, version: u64) -> crate::Result<PreparedRead> {
+        match self.view.version_tx(&record.0, version) {
+            Some(tx) => {
+                check_policy(record, self.view.version_policy(tx), Purpose::Read)?;
+                self.view.hooks.emit(Event::Read {
+                    record: *record,
+                    purpose: Purpose::Read,
+                });
+                self.prepare_blob(tx.blob)
+            }
+            None => Ok(PreparedRead::NoSuchRecord),
+        }
+    }
+
+    fn prepare_blob(&self, blob: BlobId) -> crate::Result<PreparedRead> {
+        if let Some(data) = self.view.read_cache.as_ref().and_then(|c| c.get(&blob)) {
+            return Ok(PreparedRead::CacheHit(data));
+        }
+
+        match self.view.cache.get(&blob) {
+            Some(sb) => {
+                let data = sb.decrypt(self.view.blob_key(&blob), blob)?;
+                self.cache_read(blob, &data);
+                Ok(PreparedRead::CacheHit(data))
+            }
+            None => Ok(PreparedRead::CacheMiss(ReadRequest::blob(blob))),
+        }
+    }
+
+    /// Prepare reading a chunk of a large blob. Chunks are only read through the manifest of their blob, whose policy
+    /// was checked, so the policy of the chunk isn't.
+    fn prepare_chunk(&self, chunk: &RecordId) -> crate::Result<PreparedRead> {
+        match self.view.data_tx(&chunk.0) {
+            Some(tx) if stream::is_chunk(&tx.label) => self.prepare_blob(tx.blob),
+            _ => Ok(PreparedRead::NoSuchRecord),
+        }
+    }
+
+    fn cache_read(&self, blob: BlobId, data: &[u8]) {
+        if let Some(c) = &self.view.read_cache {
+            c.insert(blob, data);
+        }
+    }
+
+    /// Open a record given a `ReadResult`.  Returns a vector of bytes. Fails with `Error::PolicyError` if the policy
+    /// of the record the data belongs to denies reading.
     pub fn read(&self, res: ReadResult) -> crate::Result<Vec<u8>> {
-        // TODO: add parameter to allow the vault to cache the result
+        self.read_use(res, Purpose::Read)
+    }
+
+    /// Open a record prepared by `prepare_use` for the given `purpose`, given a `ReadResult`.
+    pub fn read_use(&self, res: ReadResult, purpose: Purpose) -> crate::Result<Vec<u8>> {
         let b = BlobId::try_from(res.id())?;
 
-        if self.is_active_blob(&b) {
-            SealedBlob::from(res.data()).decrypt(&self.view.key, b)
-        } else {
-            Err(crate::Error::ProtocolError("invalid blob".to_string()))
+        match self
+            .blob_owners(&b)
+            .into_iter()
+            .map(|tx| check_policy(&RecordId(tx.chain), self.view.version_policy(tx), purpose))
+            .reduce(Result::or)
+        {
+            Some(allowed) => allowed?,
+            None => return Err(crate::Error::ProtocolError("invalid blob".to_string())),
+        }
+        self.open(b, &res)
+    }
+
+    /// Open a chunk of a large blob prepared by `prepare_chunk`, given a `ReadResult`.
+    fn read_chunk(&self, res: ReadResult) -> crate::Result<Vec<u8>> {
+        let b = BlobId::try_from(res.id())?;
+
+        if !self.blob_owners(&b).iter().any(|tx| stream::is_chunk(&tx.label)) {
+            return Err(crate::Error::ProtocolError("invalid blob".to_string()));
         }
+        self.open(b, &res)
+    }
+
+    fn open(&self, b: BlobId, res: &ReadResult) -> crate::Result<Vec<u8>> {
+        // TODO: add parameter to allow the vault to cache the result
+        let sb = SealedBlob::from(res.data());
+        let data = match (sb.decrypt(&self.view.key, b), &self.view.rotation) {
+            (Err(_), Some(rot)) => sb.decrypt(&rot.previous, b),
+            (res, _) => res,
+        }?;
+        self.cache_read(b, &data);
+        Ok(data)
     }
 
-    fn is_active_blob(&self, bid: &BlobId) -> bool {
+    /// Get the current and retained data transactions which refer to the blob `bid`.
+    fn blob_owners(&self, bid: &BlobId) -> Vec<&DataTransaction> {
         self.view
             .blobs
             .get(bid)
             .map(|txs| {
-                txs.iter().any(|t0| {
-                    self.view
-                        .txs
-                        .get(t0)
-                        .and_then(|tx| tx.typed::<DataTransaction>())
-                        .and_then(|tx| if tx.blob == *bid { Some(tx.chain) } else { None })
-                        .and_then(|cid| self.view.chains.get(&cid))
-                        .and_then(|c| c.data())
-                        .map(|t1| *t0 == t1)
-                        .unwrap_or(false)
-                })
+                txs.iter()
+                    .filter_map(|t0| {
+                        self.view
+                            .txs
+                            .get(t0)
+                            .and_then(|tx| tx.typed::<DataTransaction>())
+                            .filter(|tx| tx.blob == *bid)
+                            .filter(|tx| {
+                                self.view
+                                    .chains
+                                    .get(&tx.chain)
+                                    .map(|c| c.data() == Some(*t0) || c.history().contains(t0))
+                                    .unwrap_or(false)
+                            })
+                    })
+                    .collect()
             })
-            .unwrap_or(false)
+            .unwrap_or_default()
     }
 
     pub fn exists(&self, id: RecordId) -> bool {
@@ -314,9 +931,84 @@ pub struct DBWriter<'a, P: BoxProvider> {
     view: &'a DBView<P>,
     chain: ChainId,
     next_ctr: Val,
+    label: Option<RecordLabel>,
+    created: Option<u64>,
+    expires: Option<u64>,
+    history: Option<u64>,
+    policy: RecordPolicy,
+    metered: bool,
+    silent: bool,
 }
 
 impl<'a, P: BoxProvider> DBWriter<'a, P> {
+    /// Set the label of the data written by this writer. Without a label, writes keep the record's current label.
+    pub fn with_label(mut self, label: RecordLabel) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Let the data written by this writer expire after `ttl`. Once expired, the record is treated as revoked and its
+    /// transactions are collected by the next garbage collection. Without a TTL the written data doesn't expire.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.expires = Some(self.view.now().saturating_add(ttl.as_secs()));
+        self
+    }
+
+    /// Restrict the uses of the record. Policies can only be tightened: the written policy also denies every purpose
+    /// the record's current policy denies.
+    pub fn with_policy(mut self, policy: RecordPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Retain up to `depth` previous versions of the record when writing. Without a depth, writes keep the record's
+    /// current depth, which is zero for new records.
+    pub fn with_history(mut self, depth: u64) -> Self {
+        self.history = Some(depth);
+        self
+    }
+
+    /// Set the expiration time of the data written by this writer, used to carry expirations over from other vaults.
+    pub(crate) fn with_expires(mut self, expires: Option<u64>) -> Self {
+        self.expires = expires;
+        self
+    }
+
+    /// Set the creation time of the data written by this writer, used to carry timestamps over from other vaults.
+    pub(crate) fn with_created(mut self, created: u64) -> Self {
+        self.created = Some(created);
+        self
+    }
+
+    /// Don't count the writes of this writer against the quota, used by bulk operations which reserve all of their
+    /// writes at once.
+    pub(crate) fn unmetered(mut self) -> Self {
+        self.metered = false;
+        self
+    }
+
+    /// Don't emit the events of this writer, used for the chunks of large blobs and by bulk operations which emit the
+    /// events of all their writes once they succeeded.
+    pub(crate) fn silent(mut self) -> Self {
+        self.silent = true;
+        self
+    }
+
+    fn emit(&self, event: Event) {
+        if !self.silent {
+            self.view.hooks.emit(event);
+        }
+    }
+
+    /// Count a write of `bytes` encrypted bytes against the quota, `adds` is set if it writes the data of a record
+    /// which counts as one.
+    fn reserve(&self, adds: bool, bytes: usize) -> crate::Result<()> {
+        match self.metered {
+            true => self.view.reserve(Some(self.chain).filter(|_| adds), bytes),
+            false => Ok(()),
+        }
+    }
+
     fn next_ctr(&mut self) -> Val {
         let c = self.next_ctr;
         self.next_ctr += 1;
@@ -326,8 +1018,12 @@ impl<'a, P: BoxProvider> DBWriter<'a, P> {
     /// Create a new empty record or truncate an existing one
     pub fn truncate(&mut self) -> crate::Result<WriteRequest> {
         let id = TransactionId::random::<P>()?;
-        let tx = InitTransaction::new(self.chain, id, self.next_ctr());
-        Ok(WriteRequest::transaction(&id, &tx.encrypt(&self.view.key, id)?))
+        let tx = InitTransaction::new(self.chain, id, self.next_ctr(), P::box_cipher_suite());
+        let req = WriteRequest::transaction(&id, &tx.encrypt(&self.view.key, id)?);
+
+        self.reserve(false, req.data().len())?;
+        self.emit(Event::Truncated(RecordId(self.chain)));
+        Ok(req)
     }
 
     /// Check the balance of the amount of valid records compared to amount of total records in this chain
@@ -342,18 +1038,111 @@ impl<'a, P: BoxProvider> DBWriter<'a, P> {
     pub fn write(&mut self, data: &[u8], hint: RecordHint) -> crate::Result<Vec<WriteRequest>> {
         let tx_id = TransactionId::random::<P>()?;
         let blob_id = BlobId::random::<P>()?;
-        let transaction = DataTransaction::new(self.chain, self.next_ctr(), tx_id, blob_id, hint);
+
+        let current = self.view.data_tx(&self.chain).map(|tx| tx.metadata());
+        let metadata = self.next_metadata(current, None);
+        let transaction = DataTransaction::new(self.chain, self.next_ctr(), tx_id, blob_id, hint, &metadata);
 
         let req = WriteRequest::transaction(&tx_id, &transaction.encrypt(&self.view.key, tx_id)?);
         let blob = WriteRequest::blob(&blob_id, &data.encrypt(&self.view.key, blob_id)?);
 
+        self.reserve(!stream::is_chunk(&metadata.label), req.data().len() + blob.data().len())?;
+        self.emit(Event::Written(RecordId(self.chain)));
         Ok(vec![req, blob])
     }
 
+    /// Restore the retained version `version` of the record, as listed by `DBView::history`. The restored data becomes
+    /// the current data and refers to the existing blob, so nothing but the transaction is written.
+    pub fn restore(&mut self, version: u64) -> crate::Result<WriteRequest> {
+        let (blob_id, hint, label) = match self.view.version_tx(&self.chain, version) {
+            Some(tx) => (tx.blob, tx.record_hint, tx.label),
+            None => {
+                return Err(crate::Error::DatabaseError(format!(
+                    "record {} has no version {}",
+                    RecordId(self.chain),
+                    version
+                )))
+            }
+        };
+
+        let tx_id = TransactionId::random::<P>()?;
+        let current = self.view.data_tx(&self.chain).map(|tx| tx.metadata());
+        let metadata = self.next_metadata(current, Some(label));
+        let transaction = DataTransaction::new(self.chain, self.next_ctr(), tx_id, blob_id, hint, &metadata);
+
+        let req = WriteRequest::transaction(&tx_id, &transaction.encrypt(&self.view.key, tx_id)?);
+        self.reserve(!stream::is_chunk(&metadata.label), req.data().len())?;
+        self.emit(Event::Written(RecordId(self.chain)));
+        Ok(req)
+    }
+
+    /// Get the metadata for the next data transaction from the writer's settings and the record's `current` metadata.
+    /// The `label` is used if the writer has no label set.
+    fn next_metadata(&self, current: Option<RecordMetadata>, label: Option<RecordLabel>) -> RecordMetadata {
+        let now = self.view.now();
+        RecordMetadata {
+            created: self
+                .created
+                .or_else(|| current.map(|m| m.created))
+                .filter(|c| *c != 0)
+                .unwrap_or(now),
+            modified: now,
+            label: self
+                .label
+                .or(label)
+                .or_else(|| current.map(|m| m.label))
+                .unwrap_or_default(),
+            expires: self.expires,
+            history: self.history.or_else(|| current.map(|m| m.history)).unwrap_or(0),
+            policy: self.policy.restrict(current.map(|m| m.policy).unwrap_or_default()),
+        }
+    }
+
     /// Revoke a record.
     pub fn revoke(&mut self) -> crate::Result<WriteRequest> {
         let id = TransactionId::random::<P>()?;
         let tx = RevocationTransaction::new(self.chain, self.next_ctr(), id);
-        Ok(WriteRequest::transaction(&id, &tx.encrypt(&self.view.key, id)?))
+        let req = WriteRequest::transaction(&id, &tx.encrypt(&self.view.key, id)?);
+        if let (Some(cache), Some(c)) = (&self.view.read_cache, self.view.chains.get(&self.chain)) {
+            c.data()
+                .iter()
+                .chain(c.history().iter())
+                .filter_map(|tx_id| self.view.txs.get(tx_id))
+                .filter_map(|tx| tx.typed::<DataTransaction>())
+                .for_each(|tx| cache.invalidate(&tx.blob));
+        }
+        self.emit(Event::Revoked(RecordId(self.chain)));
+        Ok(req)
+    }
+}
+
+/// Get the blobs referred to by the current or a retained version of a record.
+fn live_blobs(chains: &BTreeMap<ChainId, chain::Chain>, txs: &HashMap<TransactionId, Transaction>) -> BTreeSet<BlobId> {
+    chains
+        .values()
+        .flat_map(|c| c.data().into_iter().chain(c.history().iter().cloned()))
+        .filter_map(|tx_id| txs.get(&tx_id))
+        .filter_map(|tx| tx.typed::<DataTransaction>())
+        .map(|tx| tx.blob)
+        .collect()
+}
+
+/// Check that the `policy` of a record allows the `purpose`.
+fn check_policy(record: &RecordId, policy: RecordPolicy, purpose: Purpose) -> crate::Result<()> {
+    if policy.allows(purpose) {
+        Ok(())
+    } else {
+        Err(crate::Error::PolicyError(format!(
+            "record {} may not be used for {:?}",
+            record, purpose
+        )))
     }
 }
+
+/// Get the current time in seconds since the unix epoch.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
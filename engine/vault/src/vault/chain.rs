@@ -2,13 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::types::{
-    transactions::{Transaction, TransactionType},
+    transactions::{DataTransaction, Transaction, TransactionType},
     utils::{TransactionId, Val},
 };
 
 pub struct Chain {
     garbage: Vec<TransactionId>,
     subchain: Vec<TransactionId>,
+    history: Vec<TransactionId>,
     init: Option<TransactionId>,
     data: Option<TransactionId>,
     highest_ctr: Option<Val>,
@@ -36,29 +37,38 @@ impl Chain {
         &self.subchain
     }
 
+    /// The data transactions replaced by the current one which are retained, newest first.
+    pub fn history(&self) -> &Vec<TransactionId> {
+        &self.history
+    }
+
     pub fn len(&self) -> usize {
         self.subchain.len()
     }
 
     pub fn balance(&self) -> (usize, usize) {
-        (self.len(), self.len() + self.garbage.len())
+        (self.len(), self.len() + self.history.len() + self.garbage.len())
     }
 
-    pub fn prune<'a>(chain: impl Iterator<Item = &'a Transaction>) -> crate::Result<Chain> {
+    /// Prune the chain as of `now`, records whose data expired by then are treated as revoked.
+    pub fn prune<'a>(chain: impl Iterator<Item = &'a Transaction>, now: u64) -> crate::Result<Chain> {
         let mut res = Chain {
             garbage: vec![],
             subchain: vec![],
+            history: vec![],
             init: None,
             data: None,
             highest_ctr: None,
         };
 
-        let mut chain: Vec<_> = chain.map(|tx| tx.untyped()).collect();
-        chain.sort_by_key(|tx| tx.ctr);
+        let mut chain: Vec<_> = chain.collect();
+        chain.sort_by_key(|tx| tx.untyped().ctr);
 
         let mut revocation_score = 0;
         let mut revokes = vec![];
-        for tx in chain {
+        let mut expired = false;
+        for typed in chain {
+            let tx = typed.untyped();
             res.highest_ctr = Some(tx.ctr); // NB assumed to sorted ascending
 
             if res.init.is_none() {
@@ -72,11 +82,20 @@ impl Chain {
             } else {
                 match tx.r#type()? {
                     TransactionType::Data => {
+                        let data = typed.typed::<DataTransaction>();
                         if let Some(previous) = res.data {
-                            res.garbage.push(previous);
+                            res.history.insert(0, previous);
                             res.subchain.retain(|i| i != &previous);
                         }
+
+                        // the latest data transaction decides how many versions are retained
+                        let depth = data.map(|d| d.history.u64() as usize).unwrap_or(0);
+                        while res.history.len() > depth {
+                            res.garbage.extend(res.history.pop());
+                        }
+
                         res.data = Some(tx.id);
+                        expired = data.map(|d| d.is_expired(now)).unwrap_or(false);
 
                         // Scenario: init, data, revoke, data
                         // that is: data cancels revoke
@@ -84,9 +103,11 @@ impl Chain {
                     }
                     TransactionType::Init => {
                         res.garbage.append(&mut res.subchain);
+                        res.garbage.append(&mut res.history);
                         res.init = Some(tx.id);
                         res.data = None;
                         revocation_score = 0;
+                        expired = false;
                     }
                     TransactionType::Revocation => {
                         revokes.push(tx.id);
@@ -104,8 +125,9 @@ impl Chain {
             }
         }
 
-        if revocation_score > 0 {
+        if revocation_score > 0 || expired {
             res.garbage.append(&mut res.subchain);
+            res.garbage.append(&mut res.history);
             res.init = None;
             res.data = None;
         } else {
@@ -0,0 +1,251 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    crypto_box::{BoxProvider, Key},
+    types::utils::{Purpose, RecordHint, RecordLabel, RecordPolicy},
+    vault::{check_policy, stream, DBView, Event, RecordId, WriteRequest},
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    convert::{TryFrom, TryInto},
+};
+
+//      Export Format
+//    +-----------------+
+//    | Magic bytes     |
+//    +=================+
+//    | Version bytes   |
+//    +=================+
+//    |   Sealed with   |
+//    |  the shared key |
+//    |  record count   |
+//    |  records...     |
+//    +-----------------+
+//
+// Each record is stored as its id, hint, label, created and modified timestamps, expiration time (zero if it doesn't
+// expire) and policy, followed by the length of its data and the data itself. Version 1 exports lack the policy. The
+// magic and version bytes are used as the associated data of the seal.
+
+/// "SHVX" in hex
+const MAGIC: [u8; 4] = [0x53, 0x48, 0x56, 0x58];
+/// version 2 in hex
+const VERSION: [u8; 2] = [0x0, 0x2];
+/// version 1 in hex, exports without policies
+const VERSION_1: [u8; 2] = [0x0, 0x1];
+
+/// How records of an export are imported when the vault already holds a record with the same id.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CollisionPolicy {
+    /// keep the existing record
+    Skip,
+    /// replace the existing record
+    Overwrite,
+    /// import the record under a new random id
+    Rename,
+    /// abort the import
+    Fail,
+}
+
+/// The outcome of an import.
+#[derive(Clone)]
+pub struct ImportReport {
+    /// the writes that store the imported records
+    pub writes: Vec<WriteRequest>,
+    /// the exported ids of the imported records along with the ids they were imported as
+    pub imported: Vec<(RecordId, RecordId)>,
+    /// the exported ids of the records that were skipped
+    pub skipped: Vec<RecordId>,
+}
+
+impl<P: BoxProvider> DBView<P> {
+    /// Export the valid records of the vault, including the chunks of large blobs, sealed with `key`. The key is a
+    /// symmetric key independent of the vault's key which the recipient already shares, the client derives it from a
+    /// password. Sealing it to a recipient's public key is up to the caller. The data of every record has to be loaded.
+    /// Fails with `Error::PolicyError` if any record may not be exported.
+    pub fn export(&self, key: &Key<P>) -> crate::Result<Vec<u8>> {
+        let mut plain = Vec::new();
+        let mut events = vec![];
+        let records: Vec<_> = self.entries_with_metadata().collect();
+        plain.extend_from_slice(&(records.len() as u32).to_be_bytes());
+
+        for (id, hint, metadata) in records {
+            check_policy(&id, metadata.policy, Purpose::Export)?;
+            let data = self.loaded_data(&id)?;
+            if !stream::is_chunk(&metadata.label) {
+                events.push(Event::Read {
+                    record: id,
+                    purpose: Purpose::Export,
+                });
+            }
+
+            plain.extend_from_slice(id.0.as_ref());
+            plain.extend_from_slice(hint.as_ref());
+            plain.extend_from_slice(metadata.label.as_ref());
+            plain.extend_from_slice(&metadata.created.to_be_bytes());
+            plain.extend_from_slice(&metadata.modified.to_be_bytes());
+            plain.extend_from_slice(&metadata.expires.unwrap_or(0).to_be_bytes());
+            plain.extend_from_slice(&metadata.policy.bits().to_be_bytes());
+            plain.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            plain.extend_from_slice(&data);
+        }
+
+        let mut export = header().to_vec();
+        export.append(&mut P::box_seal(key, &header(), &plain)?);
+        events.into_iter().for_each(|e| self.hooks.emit(e));
+        Ok(export)
+    }
+
+    /// Import the records of an export sealed with `key`. Records whose id is already in use are handled according to
+    /// the `policy`, a large blob imported under a new id gets copies of its chunks. The import fails with
+    /// `Error::QuotaError` if its writes together would exceed the quota.
+    pub fn import(&self, export: &[u8], key: &Key<P>, policy: CollisionPolicy) -> crate::Result<ImportReport> {
+        let (version, sealed) = match export {
+            [m0, m1, m2, m3, v0, v1, sealed @ ..] if [*m0, *m1, *m2, *m3] == MAGIC => {
+                if [*v0, *v1] != VERSION && [*v0, *v1] != VERSION_1 {
+                    return Err(crate::Error::VersionError(format!(
+                        "unsupported export version {}.{}",
+                        v0, v1
+                    )));
+                }
+                ([*v0, *v1], sealed)
+            }
+            _ => return Err(crate::Error::InterfaceError),
+        };
+
+        let plain = P::box_open(key, &export[..6], sealed)?;
+        let mut r = Cursor(&plain);
+
+        let mut entries = vec![];
+        for _ in 0..u32::from_be_bytes(r.array()?) {
+            let id = RecordId::try_from(r.take(24)?)?;
+            let hint = RecordHint::from(r.array::<[u8; 24]>()?);
+            let label = RecordLabel::from(r.array::<[u8; 24]>()?);
+            let created = u64::from_be_bytes(r.array()?);
+            let _modified = u64::from_be_bytes(r.array()?);
+            let expires = Some(u64::from_be_bytes(r.array()?)).filter(|e| *e != 0);
+            let record_policy = match version {
+                VERSION_1 => RecordPolicy::default(),
+                _ => RecordPolicy::from(u64::from_be_bytes(r.array()?)),
+            };
+            let len = u32::from_be_bytes(r.array()?) as usize;
+            let data = r.take(len)?;
+            entries.push((id, hint, label, created, expires, record_policy, data));
+        }
+
+        if !r.0.is_empty() {
+            return Err(crate::Error::DatabaseError(String::from("Invalid Export")));
+        }
+
+        let target_of = |id: RecordId| match (self.reader().exists(id), policy) {
+            (false, _) | (true, CollisionPolicy::Overwrite) => Ok(Some(id)),
+            (true, CollisionPolicy::Rename) => Ok(Some(RecordId::random::<P>()?)),
+            (true, CollisionPolicy::Skip) => Ok(None),
+            (true, CollisionPolicy::Fail) => Err(crate::Error::DatabaseError(format!("record {} already exists", id))),
+        };
+
+        // NB the chunks of a large blob follow their manifest, so a renamed blob gets chunks of its own
+        let owners = stream::chunk_owners(
+            &entries
+                .iter()
+                .map(|(id, _, label, _, _, _, data)| (*id, *label, *data))
+                .collect::<Vec<_>>(),
+        );
+        let mut decided = BTreeMap::new();
+        for (id, ..) in entries.iter().filter(|(id, ..)| !owners.contains_key(id)) {
+            decided.insert(*id, target_of(*id)?);
+        }
+        for (chunk, manifest) in owners.iter() {
+            let target = match decided[manifest] {
+                None => None,
+                Some(target) if target != *manifest => Some(RecordId::random::<P>()?),
+                Some(_) => target_of(*chunk)?,
+            };
+            decided.insert(*chunk, target);
+        }
+        let renamed: BTreeMap<_, _> = owners
+            .keys()
+            .filter_map(|c| decided[c].filter(|t| t != c).map(|t| (*c, t)))
+            .collect();
+
+        let mut report = ImportReport {
+            writes: vec![],
+            imported: vec![],
+            skipped: vec![],
+        };
+        let mut targets = vec![];
+        let mut events = vec![];
+        for (id, hint, label, created, expires, record_policy, data) in entries {
+            let target = match decided[&id] {
+                Some(target) => target,
+                None => {
+                    report.skipped.push(id);
+                    continue;
+                }
+            };
+            let data = match owners.values().any(|m| *m == id) && !renamed.is_empty() {
+                true => stream::rename_chunks(data, &renamed)?,
+                false => data.to_vec(),
+            };
+
+            let mut w = self
+                .writer(target)
+                .with_label(label)
+                .with_created(created)
+                .with_expires(expires)
+                .with_policy(record_policy)
+                .unmetered()
+                .silent();
+            let truncate = !self.reader().exists(target);
+            if truncate {
+                report.writes.push(w.truncate()?);
+            }
+            report.writes.append(&mut w.write(&data, hint)?);
+            report.imported.push((id, target));
+            if !stream::is_chunk(&label) {
+                targets.push(target.0);
+                if truncate {
+                    events.push(Event::Truncated(target));
+                }
+                events.push(Event::Written(target));
+            }
+        }
+
+        self.reserve(targets, report.writes.iter().map(|w| w.data().len()).sum())?;
+        events.into_iter().for_each(|e| self.hooks.emit(e));
+
+        Ok(report)
+    }
+}
+
+fn header() -> [u8; 6] {
+    let mut header = [0; 6];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..].copy_from_slice(&VERSION);
+    header
+}
+
+/// reads the fields of an export in order.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> crate::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(crate::Error::DatabaseError(String::from("Truncated Export")));
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<T: for<'b> TryFrom<&'b [u8]>>(&mut self) -> crate::Result<T> {
+        let len = std::mem::size_of::<T>();
+        self.take(len)?
+            .try_into()
+            .map_err(|_| crate::Error::DatabaseError(String::from("Invalid Export")))
+    }
+}
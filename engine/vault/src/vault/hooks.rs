@@ -0,0 +1,60 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{types::utils::Purpose, vault::RecordId};
+
+use std::sync::{
+    mpsc::{self, Receiver},
+    Arc, Mutex, PoisonError,
+};
+
+/// Something which happened to a vault. Events are emitted when the vault prepares the requests, applying them to
+/// the storage is up to the caller.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// data was written to the record
+    Written(RecordId),
+    /// the record was truncated
+    Truncated(RecordId),
+    /// the record was revoked
+    Revoked(RecordId),
+    /// the record was read for a purpose
+    Read { record: RecordId, purpose: Purpose },
+    /// a garbage collection run collected the given number of transactions
+    Collected { transactions: usize },
+}
+
+type Subscriber = Box<dyn FnMut(&Event) -> bool + Send>;
+
+/// The subscribers to the events of a vault. Clones share their subscribers, so the hooks can be attached to every
+/// view reloaded from the same storage.
+#[derive(Clone, Default)]
+pub struct Hooks(Arc<Mutex<Vec<Subscriber>>>);
+
+impl Hooks {
+    /// Call `f` on every event. `f` is called by the thread which caused the event and must not emit events itself.
+    pub fn subscribe(&self, f: impl Fn(&Event) + Send + 'static) {
+        self.push(Box::new(move |e| {
+            f(e);
+            true
+        }));
+    }
+
+    /// Receive the events through a channel. The channel is unsubscribed once the receiver is dropped.
+    pub fn channel(&self) -> Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.push(Box::new(move |e| tx.send(e.clone()).is_ok()));
+        rx
+    }
+
+    pub(crate) fn emit(&self, event: Event) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain_mut(|f| f(&event));
+    }
+
+    fn push(&self, subscriber: Subscriber) {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).push(subscriber);
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    types::utils::{RecordHint, RecordLabel},
+    vault::RecordId,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+/// An in-memory index of the valid records by their hint and label, built from the decrypted transactions when the
+/// vault is loaded. It isn't persisted, so writes and revocations show up in the index once the vault is reloaded.
+#[derive(Default)]
+pub struct Index {
+    hints: BTreeMap<RecordHint, BTreeSet<RecordId>>,
+    labels: BTreeMap<RecordLabel, BTreeSet<RecordId>>,
+}
+
+impl Index {
+    pub fn insert(&mut self, id: RecordId, hint: RecordHint, label: RecordLabel) {
+        self.hints.entry(hint).or_default().insert(id);
+        if !label.is_empty() {
+            self.labels.entry(label).or_default().insert(id);
+        }
+    }
+
+    pub fn hint(&self, hint: &RecordHint) -> impl Iterator<Item = RecordId> + '_ {
+        self.hints.get(hint).into_iter().flatten().copied()
+    }
+
+    pub fn hint_prefix<'a>(&'a self, prefix: &'a [u8]) -> impl Iterator<Item = RecordId> + 'a {
+        // NB hints are zero padded, so the prefix padded with zeros is the smallest hint that can match
+        let start = RecordHint::new(prefix).ok();
+        start
+            .map(|start| self.hints.range((Bound::Included(start), Bound::Unbounded)))
+            .into_iter()
+            .flatten()
+            .take_while(move |(h, _)| h.as_ref().starts_with(prefix))
+            .flat_map(|(_, ids)| ids.iter().copied())
+    }
+
+    pub fn label(&self, label: &RecordLabel) -> impl Iterator<Item = RecordId> + '_ {
+        self.labels.get(label).into_iter().flatten().copied()
+    }
+}
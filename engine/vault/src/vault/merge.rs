@@ -0,0 +1,230 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    crypto_box::BoxProvider,
+    types::utils::Purpose,
+    vault::{check_policy, stream, DBView, Event, RecordId, RecordVersion, WriteRequest},
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+/// A record which holds different data in the two merged vaults.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Conflict {
+    /// the id of the record
+    pub id: RecordId,
+    /// the record in the vault that is merged into
+    pub ours: RecordVersion,
+    /// the record in the vault that is merged from
+    pub theirs: RecordVersion,
+}
+
+/// How a conflict is resolved.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Resolution {
+    /// keep our record
+    KeepOurs,
+    /// replace our record with theirs
+    TakeTheirs,
+    /// keep our record and add theirs under a new random id
+    KeepBoth,
+}
+
+/// Decides how conflicting records are merged.
+pub trait ConflictResolver {
+    fn resolve(&self, conflict: &Conflict) -> Resolution;
+}
+
+impl<F: Fn(&Conflict) -> Resolution> ConflictResolver for F {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        self(conflict)
+    }
+}
+
+/// Resolves conflicts in favor of the record with the higher counter, keeping ours on ties.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LatestWins;
+
+impl ConflictResolver for LatestWins {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        if conflict.theirs.ctr > conflict.ours.ctr {
+            Resolution::TakeTheirs
+        } else {
+            Resolution::KeepOurs
+        }
+    }
+}
+
+/// Resolves conflicts by keeping both records.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KeepBoth;
+
+impl ConflictResolver for KeepBoth {
+    fn resolve(&self, _: &Conflict) -> Resolution {
+        Resolution::KeepBoth
+    }
+}
+
+/// A machine readable summary of a merge.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// records only present in their vault which were added
+    pub added: Vec<RecordId>,
+    /// records holding the same data in both vaults
+    pub unchanged: Vec<RecordId>,
+    /// conflicting records where ours was kept
+    pub kept: Vec<RecordId>,
+    /// conflicting records which were replaced by theirs
+    pub updated: Vec<RecordId>,
+    /// conflicting records where theirs was added under a new id, along with that id
+    pub duplicated: Vec<(RecordId, RecordId)>,
+}
+
+/// The outcome of a merge.
+#[derive(Clone)]
+pub struct Merge {
+    /// the writes that apply the merge to our vault
+    pub writes: Vec<WriteRequest>,
+    /// the summary of the merge
+    pub report: MergeReport,
+}
+
+impl<P: BoxProvider> DBView<P> {
+    /// Merge the valid records of `other`, including the chunks of large blobs, into this vault, resolving records
+    /// which hold different data in both vaults with the `resolver`. A large blob kept under a new id gets copies of
+    /// its chunks. The data of every record involved has to be loaded. Fails with `Error::PolicyError` if a record of
+    /// `other` may not be exported and with `Error::QuotaError` if the writes together would exceed the quota.
+    pub fn merge(&self, other: &DBView<P>, resolver: &impl ConflictResolver) -> crate::Result<Merge> {
+        let mut merge = Merge {
+            writes: vec![],
+            report: MergeReport::default(),
+        };
+
+        let mut entries = vec![];
+        for (id, hint, metadata) in other.entries_with_metadata() {
+            check_policy(&id, metadata.policy, Purpose::Export)?;
+            entries.push((id, hint, metadata, other.loaded_data(&id)?));
+        }
+
+        // NB the chunks of a large blob follow their manifest, so a copied blob gets chunks of its own
+        let owners = stream::chunk_owners(
+            &entries
+                .iter()
+                .map(|(id, _, metadata, data)| (*id, metadata.label, data.as_slice()))
+                .collect::<Vec<_>>(),
+        );
+        let mut renamed = BTreeMap::new();
+        let mut planned = vec![];
+        for (id, hint, metadata, data) in entries.iter().filter(|(id, ..)| !owners.contains_key(id)) {
+            let theirs = RecordVersion {
+                ctr: other.chain_ctr(id),
+                hint: *hint,
+                metadata: *metadata,
+            };
+
+            let (target, truncate) = match self.data_tx(&id.0) {
+                None => {
+                    merge.report.added.push(*id);
+                    (*id, !self.reader().exists(*id))
+                }
+                Some(tx) => {
+                    let ours = RecordVersion {
+                        ctr: self.chain_ctr(id),
+                        hint: tx.record_hint,
+                        metadata: tx.metadata(),
+                    };
+
+                    if ours.hint == theirs.hint && self.loaded_data(id)? == *data {
+                        merge.report.unchanged.push(*id);
+                        continue;
+                    }
+
+                    match resolver.resolve(&Conflict { id: *id, ours, theirs }) {
+                        Resolution::KeepOurs => {
+                            merge.report.kept.push(*id);
+                            continue;
+                        }
+                        Resolution::TakeTheirs => {
+                            merge.report.updated.push(*id);
+                            (*id, false)
+                        }
+                        Resolution::KeepBoth => {
+                            let copy = RecordId::random::<P>()?;
+                            merge.report.duplicated.push((*id, copy));
+                            (copy, true)
+                        }
+                    }
+                }
+            };
+
+            let data = if target != *id && owners.values().any(|m| m == id) {
+                for (chunk, _) in owners.iter().filter(|(_, m)| *m == id) {
+                    renamed.insert(*chunk, RecordId::random::<P>()?);
+                }
+                stream::rename_chunks(data, &renamed)?
+            } else {
+                data.clone()
+            };
+            planned.push((target, truncate, *hint, *metadata, data));
+        }
+
+        for (id, hint, metadata, data) in entries.iter().filter(|(id, ..)| owners.contains_key(id)) {
+            let (target, truncate) = match renamed.get(id) {
+                Some(copy) => {
+                    merge.report.duplicated.push((*id, *copy));
+                    (*copy, true)
+                }
+                None if self.data_tx(&id.0).is_none() => {
+                    merge.report.added.push(*id);
+                    (*id, !self.reader().exists(*id))
+                }
+                // NB chunks are never rewritten, so both vaults hold the same data
+                None => {
+                    merge.report.unchanged.push(*id);
+                    continue;
+                }
+            };
+            planned.push((target, truncate, *hint, *metadata, data.clone()));
+        }
+
+        let mut targets = vec![];
+        let mut events = vec![];
+        for (target, truncate, hint, metadata, data) in planned {
+            let mut w = self
+                .writer(target)
+                .with_label(metadata.label)
+                .with_created(metadata.created)
+                .with_expires(metadata.expires)
+                .with_policy(metadata.policy)
+                .unmetered()
+                .silent();
+            if truncate {
+                merge.writes.push(w.truncate()?);
+            }
+            merge.writes.append(&mut w.write(&data, hint)?);
+            if !stream::is_chunk(&metadata.label) {
+                targets.push(target.0);
+                if truncate {
+                    events.push(Event::Truncated(target));
+                }
+                events.push(Event::Written(target));
+            }
+        }
+
+        self.reserve(targets, merge.writes.iter().map(|w| w.data().len()).sum())?;
+        events.into_iter().for_each(|e| self.hooks.emit(e));
+        Ok(merge)
+    }
+
+    /// Get the highest counter of a record's chain.
+    fn chain_ctr(&self, id: &RecordId) -> u64 {
+        self.chains
+            .get(&id.0)
+            .and_then(|c| c.highest_ctr())
+            .map(|ctr| ctr.u64())
+            .unwrap_or(0)
+    }
+}
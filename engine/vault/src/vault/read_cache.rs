@@ -0,0 +1,161 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::types::utils::BlobId;
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use zeroize::Zeroize;
+
+/// The counters of a `ReadCache`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReadCacheStats {
+    /// reads served from the cache
+    pub hits: u64,
+    /// reads which had to decrypt the record
+    pub misses: u64,
+    /// entries dropped to stay within the size limit
+    pub evictions: u64,
+    /// number of cached records
+    pub entries: usize,
+    /// bytes of cached data
+    pub bytes: usize,
+}
+
+/// A cache of recently decrypted record data, limited to `max_bytes` of data. The least recently used data is evicted
+/// first and zeroed when it is dropped. Clones of the cache can be attached to every view of the same vault: revoking a
+/// record drops its data, and attaching the cache to a reloaded view drops the data no valid record refers to anymore.
+#[derive(Clone)]
+pub struct ReadCache(Arc<Mutex<Inner>>);
+
+struct Inner {
+    max_bytes: usize,
+    entries: HashMap<BlobId, (Vec<u8>, u64)>,
+    recency: BTreeMap<u64, BlobId>,
+    tick: u64,
+    stats: ReadCacheStats,
+}
+
+impl ReadCache {
+    /// Create a cache which holds at most `max_bytes` of decrypted data.
+    pub fn new(max_bytes: usize) -> Self {
+        Self(Arc::new(Mutex::new(Inner {
+            max_bytes,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: ReadCacheStats::default(),
+        })))
+    }
+
+    /// Get the counters of the cache.
+    pub fn stats(&self) -> ReadCacheStats {
+        self.lock().stats
+    }
+
+    /// Drop and zero all cached data.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        for (_, (mut data, _)) in inner.entries.drain() {
+            data.zeroize();
+        }
+        inner.recency.clear();
+        inner.stats.entries = 0;
+        inner.stats.bytes = 0;
+    }
+
+    /// Drop and zero the data of `blob`.
+    pub(crate) fn invalidate(&self, blob: &BlobId) {
+        self.lock().remove(blob);
+    }
+
+    /// Drop and zero the data of every blob not in `keep`.
+    pub(crate) fn retain(&self, keep: &BTreeSet<BlobId>) {
+        let mut inner = self.lock();
+        let stale: Vec<_> = inner.entries.keys().filter(|b| !keep.contains(*b)).copied().collect();
+        for blob in stale {
+            inner.remove(&blob);
+        }
+    }
+
+    pub(crate) fn get(&self, blob: &BlobId) -> Option<Vec<u8>> {
+        let mut inner = self.lock();
+        let tick = inner.next_tick();
+        let hit = inner.entries.get_mut(blob).map(|(data, last)| {
+            let previous = std::mem::replace(last, tick);
+            (data.clone(), previous)
+        });
+
+        match hit {
+            Some((data, previous)) => {
+                inner.recency.remove(&previous);
+                inner.recency.insert(tick, *blob);
+                inner.stats.hits += 1;
+                Some(data)
+            }
+            None => {
+                inner.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub(crate) fn insert(&self, blob: BlobId, data: &[u8]) {
+        let mut inner = self.lock();
+        if data.len() > inner.max_bytes || inner.entries.contains_key(&blob) {
+            return;
+        }
+
+        while inner.stats.bytes + data.len() > inner.max_bytes {
+            inner.evict();
+        }
+
+        let tick = inner.next_tick();
+        inner.entries.insert(blob, (data.to_vec(), tick));
+        inner.recency.insert(tick, blob);
+        inner.stats.entries += 1;
+        inner.stats.bytes += data.len();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Inner {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn evict(&mut self) {
+        let oldest = match self.recency.keys().next() {
+            Some(tick) => *tick,
+            None => return,
+        };
+        if let Some(blob) = self.recency.get(&oldest).copied() {
+            self.remove(&blob);
+            self.stats.evictions += 1;
+        }
+    }
+
+    fn remove(&mut self, blob: &BlobId) {
+        if let Some((mut data, tick)) = self.entries.remove(blob) {
+            self.recency.remove(&tick);
+            self.stats.entries -= 1;
+            self.stats.bytes -= data.len();
+            data.zeroize();
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        for (data, _) in self.entries.values_mut() {
+            data.zeroize();
+        }
+    }
+}
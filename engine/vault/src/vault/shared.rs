@@ -0,0 +1,39 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{crypto_box::BoxProvider, vault::DBView};
+
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+/// A `DBView` shared between threads. Readers take a snapshot of the current view and read from it without holding
+/// any lock, so they never wait on a writer that is preparing or reloading. Writers are serialized: each one works
+/// against the latest view and publishes the reloaded view once its writes have been applied.
+pub struct SharedView<P: BoxProvider> {
+    current: RwLock<Arc<DBView<P>>>,
+    writer: Mutex<()>,
+}
+
+impl<P: BoxProvider> SharedView<P> {
+    /// Share a view.
+    pub fn new(view: DBView<P>) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(view)),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Get a snapshot of the latest published view.
+    pub fn snapshot(&self) -> Arc<DBView<P>> {
+        // NB the views are immutable, so a poisoned lock can't hold a partially updated view
+        self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Run `f` as the only writer. `f` receives the latest view, applies its writes to the storage and returns the
+    /// reloaded view, which is published to readers if `f` succeeds.
+    pub fn write<T>(&self, f: impl FnOnce(&DBView<P>) -> crate::Result<(DBView<P>, T)>) -> crate::Result<T> {
+        let _guard = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let (view, res) = f(&self.snapshot())?;
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(view);
+        Ok(res)
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    crypto_box::BoxProvider,
+    types::utils::{Purpose, RecordHint, RecordLabel, RecordPolicy},
+    vault::{DBView, DBWriter, PreparedRead, ReadRequest, ReadResult, RecordId, WriteRequest},
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::{TryFrom, TryInto},
+    io,
+};
+
+//      Manifest Format
+//    +-----------------+
+//    | Magic bytes     |
+//    +=================+
+//    | Version bytes   |
+//    +=================+
+//    | total length    |
+//    | chunk count     |
+//    | chunk ids...    |
+//    +-----------------+
+//
+// A large blob is split into chunks of at most `CHUNK_SIZE` bytes, each stored as a record of its own under a random
+// id. The manifest is the data of the blob's record and lists the chunks in order. Chunks carry a reserved label which
+// hides them from the listings and a policy which only allows them to be exported, they are read through the manifest.
+
+/// "SHVB" in hex
+const MAGIC: [u8; 4] = [0x53, 0x48, 0x56, 0x42];
+/// version 1 in hex
+const VERSION: [u8; 2] = [0x0, 0x1];
+
+/// The size of the chunks a large blob is split into.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The reserved label of the chunks.
+const CHUNK_LABEL: [u8; 24] = *b"\xffstronghold.blob.chunk\0\0";
+
+/// Writes a large blob chunk by chunk. The writes of every complete chunk are passed to the sink right away, so only
+/// one chunk is held in memory. The blob is only readable once `finish` has written its manifest. A writer dropped
+/// without calling `finish` or `abort` leaves its chunks behind, hidden but taking up space.
+pub struct BlobWriter<'a, P: BoxProvider, S: FnMut(WriteRequest) -> crate::Result<()>> {
+    view: &'a DBView<P>,
+    id: RecordId,
+    hint: RecordHint,
+    sink: S,
+    buf: Vec<u8>,
+    chunks: Vec<DBWriter<'a, P>>,
+    len: u64,
+}
+
+/// Reads a large blob chunk by chunk. Chunks whose data wasn't loaded with the vault are requested from the fetch
+/// function when they are reached, so only one chunk is held in memory.
+pub struct BlobReader<'a, P: BoxProvider, F: FnMut(ReadRequest) -> crate::Result<ReadResult>> {
+    view: &'a DBView<P>,
+    fetch: F,
+    chunks: std::vec::IntoIter<RecordId>,
+    buf: Vec<u8>,
+    pos: usize,
+    len: u64,
+}
+
+impl<P: BoxProvider> DBView<P> {
+    /// Write a large blob as the record `id`, labeled with `hint`. The `sink` applies the writes to the storage. A
+    /// blob written over an existing one leaves the old chunks behind, so revoke the old blob with `revoke_blob` first.
+    pub fn blob_writer<S: FnMut(WriteRequest) -> crate::Result<()>>(
+        &self,
+        id: RecordId,
+        hint: RecordHint,
+        sink: S,
+    ) -> BlobWriter<'_, P, S> {
+        BlobWriter {
+            view: self,
+            id,
+            hint,
+            sink,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            chunks: vec![],
+            len: 0,
+        }
+    }
+
+    /// Read the large blob stored as the record `id`. The `fetch` function reads the data requested by the vault from
+    /// the storage.
+    pub fn blob_reader<F: FnMut(ReadRequest) -> crate::Result<ReadResult>>(
+        &self,
+        id: RecordId,
+        mut fetch: F,
+    ) -> crate::Result<BlobReader<'_, P, F>> {
+        let (len, chunks) = read_manifest(&read_record(self, id, &mut fetch)?)?;
+        Ok(BlobReader {
+            view: self,
+            fetch,
+            chunks: chunks.into_iter(),
+            buf: vec![],
+            pos: 0,
+            len,
+        })
+    }
+
+    /// Revoke the large blob stored as the record `id` along with its chunks.
+    pub fn revoke_blob(
+        &self,
+        id: RecordId,
+        mut fetch: impl FnMut(ReadRequest) -> crate::Result<ReadResult>,
+    ) -> crate::Result<Vec<WriteRequest>> {
+        let (_, chunks) = read_manifest(&read_record(self, id, &mut fetch)?)?;
+
+        let mut writes = vec![];
+        for chunk in chunks {
+            writes.push(self.writer(chunk).silent().revoke()?);
+        }
+        writes.push(self.writer(id).revoke()?);
+        Ok(writes)
+    }
+}
+
+impl<'a, P: BoxProvider, S: FnMut(WriteRequest) -> crate::Result<()>> BlobWriter<'a, P, S> {
+    /// Write the last chunk and the manifest, which makes the blob readable. Returns the length of the blob.
+    pub fn finish(mut self) -> crate::Result<u64> {
+        if !self.buf.is_empty() {
+            self.write_chunk()?;
+        }
+
+        let manifest = write_manifest(self.len, self.chunks.iter().map(|w| RecordId(w.chain)).collect());
+
+        let mut w = self.view.writer(self.id);
+        if !self.view.reader().exists(self.id) {
+            (self.sink)(w.truncate()?)?;
+        }
+        for req in w.write(&manifest, self.hint)? {
+            (self.sink)(req)?;
+        }
+
+        Ok(self.len)
+    }
+
+    /// Revoke the chunks written so far instead of writing the manifest.
+    pub fn abort(mut self) -> crate::Result<()> {
+        for mut chunk in std::mem::take(&mut self.chunks) {
+            (self.sink)(chunk.revoke()?)?;
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self) -> crate::Result<()> {
+        let mut w = self
+            .view
+            .writer(RecordId::random::<P>()?)
+            .with_label(RecordLabel::from(CHUNK_LABEL))
+            .with_policy(RecordPolicy::only(&[Purpose::Export]))
+            .silent();
+        (self.sink)(w.truncate()?)?;
+        for req in w.write(&self.buf, RecordHint::new(b"")?)? {
+            (self.sink)(req)?;
+        }
+
+        self.chunks.push(w);
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<'a, P: BoxProvider, S: FnMut(WriteRequest) -> crate::Result<()>> io::Write for BlobWriter<'a, P, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+        self.len += n as u64;
+
+        if self.buf.len() == CHUNK_SIZE {
+            self.write_chunk().map_err(io::Error::other)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // NB chunks are written as soon as they are complete and the rest is written by finish
+        Ok(())
+    }
+}
+
+impl<'a, P: BoxProvider, F: FnMut(ReadRequest) -> crate::Result<ReadResult>> BlobReader<'a, P, F> {
+    /// Get the length of the blob.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check if the blob is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'a, P: BoxProvider, F: FnMut(ReadRequest) -> crate::Result<ReadResult>> io::Read for BlobReader<'a, P, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.chunks.next() {
+                Some(chunk) => {
+                    self.buf = read_chunk(self.view, chunk, &mut self.fetch).map_err(io::Error::other)?;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn read_record<P: BoxProvider>(
+    view: &DBView<P>,
+    id: RecordId,
+    fetch: &mut impl FnMut(ReadRequest) -> crate::Result<ReadResult>,
+) -> crate::Result<Vec<u8>> {
+    let reader = view.reader();
+    match reader.prepare_read(&id)? {
+        PreparedRead::CacheHit(data) => Ok(data),
+        PreparedRead::CacheMiss(req) => reader.read(fetch(req)?),
+        _ => Err(crate::Error::DatabaseError(format!("record {} is missing", id))),
+    }
+}
+
+fn read_chunk<P: BoxProvider>(
+    view: &DBView<P>,
+    id: RecordId,
+    fetch: &mut impl FnMut(ReadRequest) -> crate::Result<ReadResult>,
+) -> crate::Result<Vec<u8>> {
+    let reader = view.reader();
+    match reader.prepare_chunk(&id)? {
+        PreparedRead::CacheHit(data) => Ok(data),
+        PreparedRead::CacheMiss(req) => reader.read_chunk(fetch(req)?),
+        _ => Err(crate::Error::DatabaseError(format!("chunk {} is missing", id))),
+    }
+}
+
+/// Check if a record is a chunk of a large blob by its `label`.
+pub(crate) fn is_chunk(label: &RecordLabel) -> bool {
+    *label == RecordLabel::from(CHUNK_LABEL)
+}
+
+/// Map the chunks of the large blobs among the `records` to the id of their manifest. A record only counts as a
+/// manifest if every chunk it lists is among the `records`.
+pub(crate) fn chunk_owners(records: &[(RecordId, RecordLabel, &[u8])]) -> BTreeMap<RecordId, RecordId> {
+    let chunks: BTreeSet<_> = records
+        .iter()
+        .filter(|(_, label, _)| is_chunk(label))
+        .map(|(id, _, _)| *id)
+        .collect();
+
+    let mut owners = BTreeMap::new();
+    for (id, _, data) in records.iter().filter(|(_, label, _)| !is_chunk(label)) {
+        if let Ok((_, listed)) = read_manifest(data) {
+            if listed.iter().all(|c| chunks.contains(c)) {
+                owners.extend(listed.into_iter().map(|c| (c, *id)));
+            }
+        }
+    }
+    owners
+}
+
+/// Replace the chunks of a `manifest` which were `renamed`.
+pub(crate) fn rename_chunks(manifest: &[u8], renamed: &BTreeMap<RecordId, RecordId>) -> crate::Result<Vec<u8>> {
+    let (len, chunks) = read_manifest(manifest)?;
+    Ok(write_manifest(
+        len,
+        chunks.into_iter().map(|c| *renamed.get(&c).unwrap_or(&c)).collect(),
+    ))
+}
+
+fn write_manifest(len: u64, chunks: Vec<RecordId>) -> Vec<u8> {
+    let mut manifest = header().to_vec();
+    manifest.extend_from_slice(&len.to_be_bytes());
+    manifest.extend_from_slice(&(chunks.len() as u32).to_be_bytes());
+    for chunk in chunks {
+        manifest.extend_from_slice(chunk.0.as_ref());
+    }
+    manifest
+}
+
+fn read_manifest(manifest: &[u8]) -> crate::Result<(u64, Vec<RecordId>)> {
+    let body = match manifest {
+        [m0, m1, m2, m3, v0, v1, body @ ..] if [*m0, *m1, *m2, *m3] == MAGIC => {
+            if [*v0, *v1] != VERSION {
+                return Err(crate::Error::VersionError(format!(
+                    "unsupported blob manifest version {}.{}",
+                    v0, v1
+                )));
+            }
+            body
+        }
+        _ => return Err(crate::Error::DatabaseError(String::from("Invalid Blob Manifest"))),
+    };
+
+    let invalid = || crate::Error::DatabaseError(String::from("Invalid Blob Manifest"));
+    if body.len() < 12 {
+        return Err(invalid());
+    }
+    let (len, body) = body.split_at(8);
+    let (count, body) = body.split_at(4);
+    let count = u32::from_be_bytes(count.try_into().map_err(|_| invalid())?) as usize;
+    if body.len() != count * 24 {
+        return Err(invalid());
+    }
+
+    let chunks = body.chunks(24).map(RecordId::try_from).collect::<crate::Result<_>>()?;
+    Ok((u64::from_be_bytes(len.try_into().map_err(|_| invalid())?), chunks))
+}
+
+fn header() -> [u8; 6] {
+    let mut header = [0; 6];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..].copy_from_slice(&VERSION);
+    header
+}
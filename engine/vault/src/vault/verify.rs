@@ -0,0 +1,157 @@
+// Copyright 2020 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    crypto_box::{BoxProvider, Decrypt, Key},
+    types::{
+        transactions::{DataTransaction, SealedBlob, SealedTransaction, Transaction, TransactionType},
+        utils::{BlobId, ChainId, TransactionId},
+    },
+    vault::{chain::Chain, timestamp, DBView, Kind, ReadResult, RecordId},
+};
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+};
+
+/// An entry of the storage which doesn't belong to a healthy vault.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Issue {
+    /// the entry can't be opened with the key, is malformed or is stored under a different id than its own
+    Corrupt { kind: Kind, id: Vec<u8>, reason: String },
+    /// the entry isn't part of any record: a transaction of a chain without an init transaction or a blob which no
+    /// transaction refers to
+    Orphaned { kind: Kind, id: Vec<u8> },
+    /// the current or a retained version of a record refers to a blob which isn't in the storage
+    MissingBlob { record: RecordId, blob: Vec<u8> },
+}
+
+/// The outcome of `DBView::verify`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VerifyReport {
+    /// the number of transactions which were checked
+    pub transactions: usize,
+    /// the number of blobs which were checked
+    pub blobs: usize,
+    /// the problems found, in the order of the reads
+    pub issues: Vec<Issue>,
+}
+
+impl VerifyReport {
+    /// Check if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl<P: BoxProvider> DBView<P> {
+    /// Verify the entries of a storage against `key` without loading them. Unlike `load`, which stops at the first
+    /// bad entry, every entry is checked and the problems are collected into the report. Nothing is modified, so this
+    /// can be run before trusting a snapshot of unknown origin.
+    pub fn verify<R: AsRef<ReadResult>>(key: &Key<P>, reads: impl Iterator<Item = R>) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        let mut txs = HashMap::new();
+        let mut chains: BTreeMap<ChainId, Vec<TransactionId>> = BTreeMap::new();
+        let mut blobs = vec![];
+
+        let corrupt = |kind, id: &[u8], reason: String| Issue::Corrupt {
+            kind,
+            id: id.to_vec(),
+            reason,
+        };
+
+        for r in reads {
+            let r = r.as_ref();
+            match r.kind() {
+                Kind::Transaction => {
+                    report.transactions += 1;
+                    let tx = TransactionId::try_from(r.id())
+                        .and_then(|id| Ok((id, SealedTransaction::from(r.data()).decrypt(key, id)?)))
+                        .and_then(|(id, tx): (_, Transaction)| match tx.untyped().r#type() {
+                            Ok(_) if tx.untyped().id != id => Err(crate::Error::DatabaseError(format!(
+                                "transaction {:?} is stored as {:?}",
+                                tx.untyped().id,
+                                id
+                            ))),
+                            Ok(_) => Ok((id, tx)),
+                            Err(e) => Err(e),
+                        });
+                    match tx {
+                        Ok((id, tx)) => {
+                            chains.entry(tx.untyped().chain).or_default().push(id);
+                            txs.insert(id, tx);
+                        }
+                        Err(e) => report.issues.push(corrupt(Kind::Transaction, r.id(), e.to_string())),
+                    }
+                }
+                Kind::Blob => {
+                    report.blobs += 1;
+                    let blob = BlobId::try_from(r.id()).and_then(|id| {
+                        let _: Vec<u8> = SealedBlob::from(r.data()).decrypt(key, id)?;
+                        Ok(id)
+                    });
+                    match blob {
+                        Ok(id) => blobs.push(id),
+                        Err(e) => report.issues.push(corrupt(Kind::Blob, r.id(), e.to_string())),
+                    }
+                }
+            }
+        }
+
+        let present: HashSet<_> = blobs.iter().collect();
+        let mut referenced = HashSet::new();
+        for (cid, ids) in chains.iter() {
+            let chain: Vec<_> = ids.iter().filter_map(|id| txs.get(id)).collect();
+            let has_init = chain
+                .iter()
+                .any(|tx| matches!(tx.untyped().r#type(), Ok(TransactionType::Init)));
+            if !has_init {
+                report.issues.extend(ids.iter().map(|id| Issue::Orphaned {
+                    kind: Kind::Transaction,
+                    id: id.as_ref().to_vec(),
+                }));
+                continue;
+            }
+
+            referenced.extend(
+                chain
+                    .iter()
+                    .filter_map(|tx| tx.typed::<DataTransaction>())
+                    .map(|tx| tx.blob),
+            );
+
+            // NB only expired records are dropped by the time, and their blobs aren't needed anymore
+            let pruned = match Chain::prune(chain.into_iter(), timestamp()) {
+                Ok(pruned) => pruned,
+                Err(_) => continue,
+            };
+            for blob in pruned
+                .data()
+                .iter()
+                .chain(pruned.history().iter())
+                .filter_map(|id| txs.get(id))
+                .filter_map(|tx| tx.typed::<DataTransaction>())
+                .map(|tx| tx.blob)
+                .filter(|blob| !present.contains(blob))
+            {
+                report.issues.push(Issue::MissingBlob {
+                    record: RecordId(*cid),
+                    blob: blob.as_ref().to_vec(),
+                });
+            }
+        }
+
+        report.issues.extend(
+            blobs
+                .iter()
+                .filter(|id| !referenced.contains(*id))
+                .map(|id| Issue::Orphaned {
+                    kind: Kind::Blob,
+                    id: id.as_ref().to_vec(),
+                }),
+        );
+
+        report
+    }
+}
@@ -6,9 +6,21 @@ use utils::provider::Provider;
 
 mod fresh;
 
-use vault::{DBView, Encrypt, Key, Kind, PreparedRead, ReadResult, RecordId, Result, WriteRequest};
-
-use std::{collections::HashMap, iter::empty};
+use vault::{
+    CipherSuite, CollisionPolicy, ConflictResolver, DBView, Encrypt, Event, Hooks, Issue, KeepBoth, Key, Kind,
+    LatestWins, PreparedRead, Purpose, Quota, ReadCache, ReadCacheStats, ReadResult, RecordHint, RecordId, RecordLabel,
+    RecordPolicy, Resolution, Result, SharedView, WriteRequest, CHUNK_SIZE,
+};
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{self, Read},
+    iter::empty,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 fn write_to_read(wr: &WriteRequest) -> ReadResult {
     ReadResult::new(wr.kind(), wr.id(), wr.data())
@@ -317,3 +329,1180 @@ fn test_storage_returns_stale_blob() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_cipher_suite_mismatch() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+    assert_eq!(v0.cipher_suite(), None);
+
+    let mut writes = vec![];
+
+    let id = RecordId::random::<Provider>()?;
+    let mut w = v0.writer(id);
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v1.cipher_suite(), Some(CipherSuite::XChaCha20Poly1305));
+
+    // a chain recorded with a suite this provider doesn't know
+    let (chain, init) = ([1; 24], [2; 24]);
+    let mut tx = Vec::new();
+    tx.extend_from_slice(&10u64.to_be_bytes());
+    tx.extend_from_slice(&init);
+    tx.extend_from_slice(&chain);
+    tx.extend_from_slice(&0u64.to_be_bytes());
+    tx.extend_from_slice(&2u64.to_be_bytes());
+    tx.resize(112, 0);
+    let reads = [ReadResult::new(
+        Kind::Transaction,
+        &init,
+        tx.encrypt(&k, init)?.as_ref(),
+    )];
+    match DBView::load(k, reads.iter()) {
+        Err(vault::Error::CipherSuiteError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_rekey() -> Result<()> {
+    let k0: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k0.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    let id = RecordId::random::<Provider>()?;
+    let mut w = v0.writer(id);
+    writes.push(w.truncate()?);
+    let data = fresh::data();
+    writes.append(&mut w.write(&data, fresh::record_hint())?);
+
+    let k1: Key<Provider> = Key::random()?;
+    assert!(DBView::load(k1.clone(), writes.iter().map(write_to_read)).is_err());
+
+    // the blobs have to be loaded, otherwise their key is unknown
+    let transactions = writes.iter().filter(|w| w.kind() == Kind::Transaction);
+    let v = DBView::load_rotating(k1.clone(), k0.clone(), transactions.map(write_to_read))?;
+    assert_eq!(v.rekey_progress().remaining, 3);
+    assert!(matches!(v.rekey(10), Err(vault::Error::ProtocolError(_))));
+
+    let mut v1 = DBView::load_rotating(k1.clone(), k0.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v1.rekey_progress().remaining, 3);
+    assert_eq!(v1.rekey_progress().total, 3);
+    assert_eq!(v1.reader().prepare_read(&id)?, PreparedRead::CacheHit(data.clone()));
+
+    while !v1.rekey_progress().is_done() {
+        let remaining = v1.rekey_progress().remaining;
+        for wr in v1.rekey(2)? {
+            writes.retain(|w| w.id() != wr.id());
+            writes.push(wr);
+        }
+
+        v1 = DBView::load_rotating(k1.clone(), k0.clone(), writes.iter().map(write_to_read))?;
+        assert!(v1.rekey_progress().remaining < remaining);
+    }
+
+    let v2 = DBView::load(k1, writes.iter().map(write_to_read))?;
+    assert_eq!(v2.rekey_progress().remaining, 0);
+    assert_eq!(v2.rekey(10)?.len(), 0);
+    assert_eq!(v2.reader().prepare_read(&id)?, PreparedRead::CacheHit(data));
+
+    Ok(())
+}
+
+#[test]
+fn test_find_by_hint() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    let mut ids = vec![];
+    for hint in &["alpha-1", "alpha-2", "beta"] {
+        let id = RecordId::random::<Provider>()?;
+        let mut w = v0.writer(id);
+        writes.push(w.truncate()?);
+        writes.append(&mut w.write(&fresh::data(), RecordHint::new(hint)?)?);
+        ids.push(id);
+    }
+
+    let v1 = DBView::load(k, writes.iter().map(write_to_read))?;
+
+    let found: Vec<_> = v1.find_by_hint(&RecordHint::new("alpha-2")?).collect();
+    assert_eq!(found, vec![ids[1]]);
+
+    let mut found: Vec<_> = v1.find_by_hint_prefix(b"alpha").collect();
+    found.sort();
+    let mut expected = vec![ids[0], ids[1]];
+    expected.sort();
+    assert_eq!(found, expected);
+
+    assert_eq!(v1.find_by_hint_prefix(b"gamma").count(), 0);
+    assert_eq!(v1.find_by_hint(&RecordHint::new("alpha")?).count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_find_by_label() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let label = RecordLabel::new("seed")?;
+    let mut writes = vec![];
+
+    let mut ids = vec![];
+    for _ in 0..3 {
+        let id = RecordId::random::<Provider>()?;
+        let mut w = v0.writer(id).with_label(label);
+        writes.push(w.truncate()?);
+        writes.append(&mut w.write(&fresh::data(), RecordHint::new("key")?)?);
+        ids.push(id);
+    }
+    ids.sort();
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v1.find_by_label(&label).collect::<Vec<_>>(), ids);
+    assert_eq!(v1.find_by_label(&RecordLabel::default()).count(), 0);
+
+    // the index follows revocations and changed hints
+    writes.push(v1.writer(ids[0]).revoke()?);
+    writes.append(&mut v1.writer(ids[1]).write(&fresh::data(), RecordHint::new("other")?)?);
+
+    let v2 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert_eq!(v2.find_by_label(&label).collect::<Vec<_>>(), vec![ids[1], ids[2]]);
+    assert_eq!(
+        v2.find_by_hint(&RecordHint::new("key")?).collect::<Vec<_>>(),
+        vec![ids[2]]
+    );
+    assert_eq!(v2.find_by_hint_prefix(b"oth").collect::<Vec<_>>(), vec![ids[1]]);
+
+    Ok(())
+}
+
+#[test]
+fn test_metadata() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    let id = RecordId::random::<Provider>()?;
+    let label = RecordLabel::new("signing key")?;
+    let mut w = v0.writer(id).with_label(label);
+    writes.push(w.truncate()?);
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v1.metadata(&id), None);
+
+    writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+
+    let v2 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let m0 = v2.metadata(&id).expect("record has metadata");
+    assert_eq!(m0.label, label);
+    assert!(m0.created > 0);
+    assert!(m0.modified >= m0.created);
+    assert_eq!(v2.records_with_metadata().collect::<Vec<_>>().len(), 1);
+
+    writes.append(&mut v2.writer(id).write(&fresh::data(), fresh::record_hint())?);
+
+    let v3 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let m1 = v3.metadata(&id).expect("record has metadata");
+    assert_eq!(m1.label, label);
+    assert_eq!(m1.created, m0.created);
+    assert!(m1.modified >= m0.modified);
+
+    writes.append(
+        &mut v3
+            .writer(id)
+            .with_label(RecordLabel::default())
+            .write(&fresh::data(), fresh::record_hint())?,
+    );
+
+    let v4 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert!(v4.metadata(&id).expect("record has metadata").label.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_load_legacy_transactions() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+
+    fn legacy_tx(type_id: u64, id: &[u8], chain: &[u8], ctr: u64, rest: &[&[u8]]) -> Vec<u8> {
+        let mut tx = Vec::new();
+        tx.extend_from_slice(&type_id.to_be_bytes());
+        tx.extend_from_slice(id);
+        tx.extend_from_slice(chain);
+        tx.extend_from_slice(&ctr.to_be_bytes());
+        rest.iter().for_each(|r| tx.extend_from_slice(r));
+        tx.resize(112, 0);
+        tx
+    }
+
+    let chain = [1; 24];
+    let (init, data, blob) = ([2; 24], [3; 24], [4; 24]);
+    let hint = fresh::record_hint();
+    let payload = fresh::data();
+
+    let reads = [
+        ReadResult::new(
+            Kind::Transaction,
+            &init,
+            legacy_tx(10, &init, &chain, 0, &[]).encrypt(&k, init)?.as_ref(),
+        ),
+        ReadResult::new(
+            Kind::Transaction,
+            &data,
+            legacy_tx(1, &data, &chain, 1, &[&blob, hint.as_ref()])
+                .encrypt(&k, data)?
+                .as_ref(),
+        ),
+        ReadResult::new(Kind::Blob, &blob, payload.encrypt(&k, blob)?.as_ref()),
+    ];
+
+    let v = DBView::load(k, reads.iter())?;
+    let id = RecordId::try_from(&chain[..])?;
+    assert_eq!(v.records().collect::<Vec<_>>(), vec![(id, hint)]);
+    assert_eq!(v.metadata(&id), Some(Default::default()));
+    assert_eq!(v.reader().prepare_read(&id)?, PreparedRead::CacheHit(payload));
+
+    Ok(())
+}
+
+#[test]
+fn test_list() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    let mut ids = vec![];
+    for _ in 0..5 {
+        let id = RecordId::random::<Provider>()?;
+        let mut w = v0.writer(id);
+        writes.push(w.truncate()?);
+        writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+        ids.push(id);
+    }
+    writes.push(v0.writer(RecordId::random::<Provider>()?).truncate()?);
+    ids.sort();
+
+    let v1 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert_eq!(v1.record_count(), 5);
+
+    let mut listed = vec![];
+    let mut page = v1.list(None, 2);
+    loop {
+        assert!(page.records.len() <= 2);
+        listed.extend(page.records.iter().map(|(id, _)| *id));
+        match page.next {
+            Some(next) => page = v1.list(Some(&next), 2),
+            None => break,
+        }
+    }
+    assert_eq!(listed, ids);
+
+    assert_eq!(v1.list(None, 10).next, None);
+    assert_eq!(v1.list(Some(&ids[4]), 10).records.len(), 0);
+
+    // a zero limit still makes progress
+    let page = v1.list(None, 0);
+    assert_eq!(page.records.len(), 1);
+    assert_eq!(page.next, Some(ids[0]));
+
+    Ok(())
+}
+
+#[test]
+fn test_gc_step() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    for _ in 0..3 {
+        let id = RecordId::random::<Provider>()?;
+        let mut w = v0.writer(id);
+        writes.push(w.truncate()?);
+        writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+        writes.push(w.revoke()?);
+    }
+
+    let mut v = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let total = v.gc().len();
+    assert_eq!(total, 9);
+
+    let mut collected = 0;
+    loop {
+        let (deletes, progress) = v.gc_step(4);
+        assert!(deletes.len() <= 4);
+        assert_eq!(progress.collected, deletes.len());
+        collected += progress.collected;
+        assert_eq!(progress.remaining, total - collected);
+
+        deletes.iter().for_each(|d| writes.retain(|w| w.id() != d.id()));
+        v = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+
+        if progress.is_done() {
+            break;
+        }
+    }
+
+    assert_eq!(v.gc().len(), 0);
+    assert_eq!(
+        v.gc_step(4).1,
+        vault::GcProgress {
+            collected: 0,
+            remaining: 0
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_view() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    let id = RecordId::random::<Provider>()?;
+    let data = fresh::data();
+    let mut w = v0.writer(id);
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&data, fresh::record_hint())?);
+
+    let shared = Arc::new(SharedView::new(DBView::load(
+        k.clone(),
+        writes.iter().map(write_to_read),
+    )?));
+
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            let data = data.clone();
+            thread::spawn(move || -> Result<()> {
+                for _ in 0..50 {
+                    let view = shared.snapshot();
+                    assert_eq!(view.reader().prepare_read(&id)?, PreparedRead::CacheHit(data.clone()));
+                    assert!(view.record_count() >= 1);
+                }
+                Ok(())
+            })
+        })
+        .collect();
+
+    for _ in 0..10 {
+        shared.write(|view| {
+            let mut w = view.writer(RecordId::random::<Provider>()?);
+            writes.push(w.truncate()?);
+            writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+            Ok((DBView::load(k.clone(), writes.iter().map(write_to_read))?, ()))
+        })?;
+    }
+
+    for r in readers {
+        r.join().expect("reader panicked")?;
+    }
+
+    assert_eq!(shared.snapshot().record_count(), 11);
+
+    Ok(())
+}
+
+#[test]
+fn test_usage_and_quota() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+    assert_eq!(v0.usage(), Default::default());
+
+    let mut writes = vec![];
+
+    let id = RecordId::random::<Provider>()?;
+    let mut w = v0.writer(id);
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let usage = v1.usage();
+    assert_eq!(usage.records, 1);
+    assert_eq!(usage.transactions, 2);
+    assert_eq!(usage.blobs, 1);
+    assert_eq!(usage.bytes, writes.iter().map(|w| w.data().len()).sum::<usize>());
+
+    let v2 = DBView::load(k.clone(), writes.iter().map(write_to_read))?.with_quota(Quota {
+        max_records: Some(1),
+        max_bytes: None,
+    });
+
+    // overwriting an existing record doesn't add a record
+    v2.writer(id).write(&fresh::data(), fresh::record_hint())?;
+
+    let mut w = v2.writer(RecordId::random::<Provider>()?);
+    w.truncate()?;
+    match w.write(&fresh::data(), fresh::record_hint()) {
+        Err(vault::Error::QuotaError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+
+    let v3 = DBView::load(k.clone(), writes.iter().map(write_to_read))?.with_quota(Quota {
+        max_records: None,
+        max_bytes: Some(usage.bytes),
+    });
+
+    match v3.writer(id).write(&fresh::data(), fresh::record_hint()) {
+        Err(vault::Error::QuotaError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+    v3.writer(id).revoke()?;
+
+    // the writes prepared by a view count against each other
+    let v4 = DBView::load(k.clone(), empty::<ReadResult>())?.with_quota(Quota {
+        max_records: Some(1),
+        max_bytes: None,
+    });
+    let mut w = v4.writer(RecordId::random::<Provider>()?);
+    w.truncate()?;
+    w.write(&fresh::data(), fresh::record_hint())?;
+    w.write(&fresh::data(), fresh::record_hint())?;
+    let mut w = v4.writer(RecordId::random::<Provider>()?);
+    w.truncate()?;
+    match w.write(&fresh::data(), fresh::record_hint()) {
+        Err(vault::Error::QuotaError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+
+    // an import is checked as a whole
+    let export = v1.export(&k)?;
+    let v5 = DBView::load(k.clone(), empty::<ReadResult>())?.with_quota(Quota {
+        max_records: None,
+        max_bytes: Some(usage.bytes - 1),
+    });
+    match v5.import(&export, &k, CollisionPolicy::Fail) {
+        Err(vault::Error::QuotaError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+
+    // unreferenced blobs don't count
+    let mut w = v1.writer(id);
+    let overwrite = w.write(&fresh::data(), fresh::record_hint())?;
+    let v6 = DBView::load(k, writes.iter().chain(overwrite.iter()).map(write_to_read))?;
+    assert_eq!(
+        v6.usage().bytes,
+        writes[..2]
+            .iter()
+            .chain(overwrite.iter())
+            .map(|w| w.data().len())
+            .sum::<usize>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export_import() -> Result<()> {
+    let ka: Key<Provider> = Key::random()?;
+    let va = DBView::load(ka.clone(), empty::<ReadResult>())?;
+
+    let mut writes_a = vec![];
+
+    let id0 = RecordId::random::<Provider>()?;
+    let id1 = RecordId::random::<Provider>()?;
+    let (data0, data1) = (fresh::data(), fresh::data());
+    let label = RecordLabel::new("exported")?;
+    let mut w = va.writer(id0).with_label(label);
+    writes_a.push(w.truncate()?);
+    writes_a.append(&mut w.write(&data0, fresh::record_hint())?);
+    let mut w = va.writer(id1);
+    writes_a.push(w.truncate()?);
+    writes_a.append(&mut w.write(&data1, fresh::record_hint())?);
+
+    let va = DBView::load(ka, writes_a.iter().map(write_to_read))?;
+    let export_key: Key<Provider> = Key::random()?;
+    let export = va.export(&export_key)?;
+
+    let kb: Key<Provider> = Key::random()?;
+    let vb = DBView::load(kb.clone(), empty::<ReadResult>())?;
+    let mut w = vb.writer(id0);
+    let mut writes_b = vec![w.truncate()?];
+    let existing = fresh::data();
+    writes_b.append(&mut w.write(&existing, fresh::record_hint())?);
+    let vb = DBView::load(kb.clone(), writes_b.iter().map(write_to_read))?;
+
+    let import = |policy| -> Result<(DBView<Provider>, vault::ImportReport)> {
+        let report = vb.import(&export, &export_key, policy)?;
+        let reads = writes_b.iter().chain(report.writes.iter()).map(write_to_read);
+        Ok((DBView::load(kb.clone(), reads)?, report))
+    };
+
+    let (v, report) = import(CollisionPolicy::Skip)?;
+    assert_eq!(report.skipped, vec![id0]);
+    assert_eq!(report.imported, vec![(id1, id1)]);
+    assert_eq!(v.reader().prepare_read(&id0)?, PreparedRead::CacheHit(existing.clone()));
+    assert_eq!(v.reader().prepare_read(&id1)?, PreparedRead::CacheHit(data1.clone()));
+
+    let (v, _) = import(CollisionPolicy::Overwrite)?;
+    assert_eq!(v.record_count(), 2);
+    assert_eq!(v.reader().prepare_read(&id0)?, PreparedRead::CacheHit(data0.clone()));
+    assert_eq!(v.metadata(&id0).expect("record has metadata").label, label);
+    assert_eq!(
+        v.metadata(&id0).map(|m| m.created),
+        va.metadata(&id0).map(|m| m.created)
+    );
+
+    let (v, report) = import(CollisionPolicy::Rename)?;
+    assert_eq!(v.record_count(), 3);
+    let renamed = report
+        .imported
+        .iter()
+        .find(|(from, _)| *from == id0)
+        .expect("record was imported")
+        .1;
+    assert_ne!(renamed, id0);
+    assert_eq!(v.reader().prepare_read(&id0)?, PreparedRead::CacheHit(existing));
+    assert_eq!(v.reader().prepare_read(&renamed)?, PreparedRead::CacheHit(data0));
+
+    assert!(import(CollisionPolicy::Fail).is_err());
+    assert!(vb.import(&export, &Key::random()?, CollisionPolicy::Skip).is_err());
+
+    let mut tampered = export.clone();
+    tampered[5] += 1;
+    match vb.import(&tampered, &export_key, CollisionPolicy::Skip) {
+        Err(vault::Error::VersionError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_merge() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let (shared, conflict, theirs_only) = (
+        RecordId::random::<Provider>()?,
+        RecordId::random::<Provider>()?,
+        RecordId::random::<Provider>()?,
+    );
+    let (shared_data, shared_hint) = (fresh::data(), fresh::record_hint());
+    let (ours_data, theirs_data, new_data) = (b"ours".to_vec(), b"theirs".to_vec(), fresh::data());
+
+    let mut ours = vec![];
+    let mut w = v0.writer(shared);
+    ours.push(w.truncate()?);
+    ours.append(&mut w.write(&shared_data, shared_hint)?);
+    let mut w = v0.writer(conflict);
+    ours.push(w.truncate()?);
+    ours.append(&mut w.write(&ours_data, fresh::record_hint())?);
+
+    let mut theirs = vec![];
+    let mut w = v0.writer(shared);
+    theirs.push(w.truncate()?);
+    theirs.append(&mut w.write(&shared_data, shared_hint)?);
+    let mut w = v0.writer(conflict);
+    theirs.push(w.truncate()?);
+    theirs.append(&mut w.write(b"older", fresh::record_hint())?);
+    theirs.append(&mut w.write(&theirs_data, fresh::record_hint())?);
+    let mut w = v0.writer(theirs_only);
+    theirs.push(w.truncate()?);
+    theirs.append(&mut w.write(&new_data, fresh::record_hint())?);
+
+    let vo = DBView::load(k.clone(), ours.iter().map(write_to_read))?;
+    let vt = DBView::load(k.clone(), theirs.iter().map(write_to_read))?;
+
+    let merged = |resolver: &dyn Fn(&vault::Conflict) -> Resolution| -> Result<(DBView<Provider>, vault::MergeReport)> {
+        let merge = vo.merge(&vt, &resolver)?;
+        let reads = ours.iter().chain(merge.writes.iter()).map(write_to_read);
+        Ok((DBView::load(k.clone(), reads)?, merge.report))
+    };
+
+    let (v, report) = merged(&|c| LatestWins.resolve(c))?;
+    assert_eq!(report.added, vec![theirs_only]);
+    assert_eq!(report.unchanged, vec![shared]);
+    assert_eq!(report.updated, vec![conflict]);
+    assert_eq!(v.record_count(), 3);
+    assert_eq!(
+        v.reader().prepare_read(&conflict)?,
+        PreparedRead::CacheHit(theirs_data.clone())
+    );
+    assert_eq!(v.reader().prepare_read(&theirs_only)?, PreparedRead::CacheHit(new_data));
+
+    let (v, report) = merged(&|c| KeepBoth.resolve(c))?;
+    let (from, copy) = report.duplicated[0];
+    assert_eq!(from, conflict);
+    assert_eq!(v.record_count(), 4);
+    assert_eq!(
+        v.reader().prepare_read(&conflict)?,
+        PreparedRead::CacheHit(ours_data.clone())
+    );
+    assert_eq!(v.reader().prepare_read(&copy)?, PreparedRead::CacheHit(theirs_data));
+
+    let (v, report) = merged(&|_| Resolution::KeepOurs)?;
+    assert_eq!(report.kept, vec![conflict]);
+    assert_eq!(v.reader().prepare_read(&conflict)?, PreparedRead::CacheHit(ours_data));
+
+    Ok(())
+}
+
+#[test]
+fn test_expiration() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+
+    let (expired, valid) = (RecordId::random::<Provider>()?, RecordId::random::<Provider>()?);
+    let mut w = v0.writer(expired).with_ttl(Duration::from_secs(0));
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+    let data = fresh::data();
+    let mut w = v0.writer(valid).with_ttl(Duration::from_secs(3600));
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&data, fresh::record_hint())?);
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v1.reader().prepare_read(&expired)?, PreparedRead::NoSuchRecord);
+    assert_eq!(v1.reader().prepare_read(&valid)?, PreparedRead::CacheHit(data));
+    assert_eq!(v1.record_count(), 1);
+    assert!(v1.metadata(&valid).and_then(|m| m.expires).is_some());
+    assert_eq!(v1.gc().len(), 2);
+
+    // writing new data revives the record
+    let data = fresh::data();
+    writes.append(&mut v1.writer(expired).write(&data, fresh::record_hint())?);
+
+    let v2 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v2.reader().prepare_read(&expired)?, PreparedRead::CacheHit(data));
+    assert_eq!(v2.metadata(&expired).and_then(|m| m.expires), None);
+
+    // a record expiring after the vault was loaded disappears from every lookup
+    let id = RecordId::random::<Provider>()?;
+    let hint = fresh::record_hint();
+    let label = RecordLabel::new("expiring")?;
+    let mut w = v2.writer(id).with_ttl(Duration::from_secs(60)).with_label(label);
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&fresh::data(), hint)?);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let v3 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert_eq!(v3.record_count(), 3);
+    let v3 = v3.with_clock(move || now + 600);
+    assert_eq!(v3.reader().prepare_read(&id)?, PreparedRead::NoSuchRecord);
+    assert_eq!(v3.record_count(), 2);
+    assert!(v3.records().all(|(r, _)| r != id));
+    assert_eq!(v3.list(None, 10).records.len(), 2);
+    assert_eq!(v3.metadata(&id), None);
+    assert_eq!(v3.find_by_hint(&hint).count(), 0);
+    assert_eq!(v3.find_by_label(&label).count(), 0);
+
+    // a TTL too long to represent doesn't overflow
+    v3.writer(id).with_ttl(Duration::MAX).write(&fresh::data(), hint)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_history() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let id = RecordId::random::<Provider>()?;
+    let mut writes = vec![];
+    let mut w = v0.writer(id).with_history(2);
+    writes.push(w.truncate()?);
+    let first = fresh::data();
+    writes.append(&mut w.write(&first, fresh::record_hint())?);
+
+    let mut versions = vec![first];
+    for _ in 0..3 {
+        let v = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+        let data = fresh::data();
+        writes.append(&mut v.writer(id).write(&data, fresh::record_hint())?);
+        versions.push(data);
+    }
+
+    // only the two latest previous versions are retained, the oldest one is garbage
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let history = v1.history(&id);
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].metadata.history, 2);
+    assert!(history[0].ctr > history[1].ctr);
+    assert_eq!(v1.gc().len(), 1);
+    assert_eq!(
+        v1.reader().prepare_read(&id)?,
+        PreparedRead::CacheHit(versions[3].clone())
+    );
+    assert_eq!(
+        v1.reader().prepare_read_version(&id, history[1].ctr)?,
+        PreparedRead::CacheHit(versions[1].clone())
+    );
+    assert_eq!(v1.reader().prepare_read_version(&id, 42)?, PreparedRead::NoSuchRecord);
+    assert!(v1.writer(id).restore(42).is_err());
+
+    writes.push(v1.writer(id).restore(history[1].ctr)?);
+
+    let v2 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert_eq!(
+        v2.reader().prepare_read(&id)?,
+        PreparedRead::CacheHit(versions[1].clone())
+    );
+    assert_eq!(v2.history(&id).len(), 2);
+    assert_eq!(v2.metadata(&id).map(|m| m.history), Some(2));
+
+    Ok(())
+}
+
+#[test]
+fn test_verify() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    // one record per problem: (init, data, blob) each
+    let mut records = vec![];
+    for _ in 0..4 {
+        let id = RecordId::random::<Provider>()?;
+        let mut w = v0.writer(id);
+        let mut writes = vec![w.truncate()?];
+        writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+        assert_eq!(
+            writes.iter().map(|w| w.kind()).collect::<Vec<_>>(),
+            vec![Kind::Transaction, Kind::Transaction, Kind::Blob]
+        );
+        records.push((id, writes));
+    }
+
+    let healthy: Vec<_> = records
+        .iter()
+        .flat_map(|(_, ws)| ws.iter().map(write_to_read))
+        .collect();
+    let report = DBView::verify(&k, healthy.iter());
+    assert!(report.is_ok());
+    assert_eq!((report.transactions, report.blobs), (8, 4));
+
+    let mut reads = vec![];
+    // a tampered data transaction
+    let (_, ws) = &records[0];
+    let mut tampered = ws[1].data().to_vec();
+    tampered[0] ^= 1;
+    reads.push(write_to_read(&ws[0]));
+    reads.push(ReadResult::new(Kind::Transaction, ws[1].id(), &tampered));
+    reads.push(write_to_read(&ws[2]));
+    // a data transaction without its init transaction
+    let (_, ws) = &records[1];
+    reads.push(write_to_read(&ws[1]));
+    reads.push(write_to_read(&ws[2]));
+    // a record whose blob is missing
+    let (missing, ws) = &records[2];
+    reads.push(write_to_read(&ws[0]));
+    reads.push(write_to_read(&ws[1]));
+    // a healthy record
+    reads.extend(records[3].1.iter().map(write_to_read));
+
+    let report = DBView::verify(&k, reads.iter());
+    assert_eq!(report.issues.len(), 5);
+    assert!(
+        matches!(&report.issues[0], Issue::Corrupt { kind: Kind::Transaction, id, .. } if id == records[0].1[1].id())
+    );
+    assert!(report.issues.contains(&Issue::Orphaned {
+        kind: Kind::Transaction,
+        id: records[1].1[1].id().to_vec()
+    }));
+    assert!(report.issues.contains(&Issue::MissingBlob {
+        record: *missing,
+        blob: records[2].1[2].id().to_vec()
+    }));
+    // the blobs of the tampered and the orphaned record aren't referenced anymore
+    assert!(report.issues.contains(&Issue::Orphaned {
+        kind: Kind::Blob,
+        id: records[0].1[2].id().to_vec()
+    }));
+    assert!(report.issues.contains(&Issue::Orphaned {
+        kind: Kind::Blob,
+        id: records[1].1[2].id().to_vec()
+    }));
+
+    // the wrong key makes every entry corrupt
+    let report = DBView::verify(&Key::<Provider>::random()?, healthy.iter());
+    assert_eq!(report.issues.len(), 12);
+
+    Ok(())
+}
+
+#[test]
+fn test_blob_stream() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut payload = vec![];
+    while payload.len() < 2 * CHUNK_SIZE + CHUNK_SIZE / 2 {
+        payload.extend(fresh::data());
+    }
+
+    let id = RecordId::random::<Provider>()?;
+    let mut writes = vec![];
+    let mut w = v0.blob_writer(id, fresh::record_hint(), |req| {
+        writes.push(req);
+        Ok(())
+    });
+    io::copy(&mut payload.as_slice(), &mut w).unwrap();
+    assert_eq!(w.finish()?, payload.len() as u64);
+    // three chunks and the manifest
+    assert_eq!(writes.iter().filter(|w| w.kind() == Kind::Blob).count(), 4);
+
+    // only load the transactions, the chunks are fetched while reading
+    let storage: HashMap<_, _> = writes.iter().map(|w| (w.id().to_vec(), w.data().to_vec())).collect();
+    let fetch = |req: vault::ReadRequest| Ok(req.result(storage[req.id()].clone()));
+    let v1 = DBView::load(
+        k.clone(),
+        writes
+            .iter()
+            .filter(|w| w.kind() == Kind::Transaction)
+            .map(write_to_read),
+    )?;
+
+    let mut r = v1.blob_reader(id, fetch)?;
+    assert_eq!(r.len(), payload.len() as u64);
+    let mut read = vec![];
+    r.read_to_end(&mut read).unwrap();
+    assert_eq!(read, payload);
+
+    // the chunks are hidden and only readable through the blob
+    assert_eq!(v1.record_count(), 1);
+    assert_eq!(v1.records().count(), 1);
+    assert_eq!(v1.list(None, 10).records.len(), 1);
+    assert_eq!(v1.find_by_hint_prefix(b"").count(), 1);
+    let chunk = v1.all().find(|c| *c != id).unwrap();
+    assert_eq!(v1.metadata(&chunk), None);
+    match v1.reader().prepare_read(&chunk) {
+        Err(vault::Error::PolicyError(_)) => (),
+        Err(_) | Ok(_) => panic!("unexpected result"),
+    }
+
+    // an aborted writer revokes its chunks
+    let mut aborted = vec![];
+    let mut w = v1.blob_writer(RecordId::random::<Provider>()?, fresh::record_hint(), |req| {
+        aborted.push(req);
+        Ok(())
+    });
+    io::copy(&mut payload.as_slice(), &mut w).unwrap();
+    w.abort()?;
+    let v = DBView::load(k.clone(), writes.iter().chain(aborted.iter()).map(write_to_read))?;
+    assert_eq!(v.record_count(), 1);
+    assert_eq!(v.gc().len(), 6);
+
+    // a missing chunk fails the read
+    let mut r = v1.blob_reader(id, |req: vault::ReadRequest| {
+        if storage[req.id()].len() > CHUNK_SIZE / 2 {
+            Err(vault::Error::DatabaseError(String::from("unavailable")))
+        } else {
+            Ok(req.result(storage[req.id()].clone()))
+        }
+    })?;
+    assert!(r.read_to_end(&mut vec![]).is_err());
+
+    writes.append(&mut v1.revoke_blob(id, fetch)?);
+    let v2 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert_eq!(v2.record_count(), 0);
+    assert!(v2.blob_reader(id, fetch).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_blob_copies() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+    let fetch = |_: vault::ReadRequest| -> Result<ReadResult> { Err(vault::Error::InterfaceError) };
+    let read = |v: &DBView<Provider>, id: RecordId| -> Result<Vec<u8>> {
+        let mut data = vec![];
+        v.blob_reader(id, fetch)?.read_to_end(&mut data).unwrap();
+        Ok(data)
+    };
+
+    let payload = vec![7; CHUNK_SIZE + 1];
+    let id = RecordId::random::<Provider>()?;
+    let mut writes = vec![];
+    let mut w = v0.blob_writer(id, fresh::record_hint(), |req| {
+        writes.push(req);
+        Ok(())
+    });
+    io::copy(&mut payload.as_slice(), &mut w).unwrap();
+    w.finish()?;
+
+    // a blob imported under a new id gets chunks of its own
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let report = v1.import(&v1.export(&k)?, &k, CollisionPolicy::Rename)?;
+    let (_, copy) = report.imported.iter().find(|(from, _)| *from == id).copied().unwrap();
+    assert_ne!(copy, id);
+    writes.extend(report.writes);
+
+    let v2 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v2.record_count(), 2);
+    writes.append(&mut v2.revoke_blob(id, fetch)?);
+    let v3 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert!(v3.blob_reader(id, fetch).is_err());
+    assert_eq!(read(&v3, copy)?, payload);
+
+    // so does a conflicting blob kept under a new id by a merge
+    let theirs = vec![9; CHUNK_SIZE + 1];
+    let mut writes_b = vec![];
+    let mut w = v0.blob_writer(copy, fresh::record_hint(), |req| {
+        writes_b.push(req);
+        Ok(())
+    });
+    io::copy(&mut theirs.as_slice(), &mut w).unwrap();
+    w.finish()?;
+
+    let vb = DBView::load(k.clone(), writes_b.iter().map(write_to_read))?;
+    let merge = v3.merge(&vb, &KeepBoth)?;
+    let (_, kept) = merge
+        .report
+        .duplicated
+        .iter()
+        .find(|(from, _)| *from == copy)
+        .copied()
+        .unwrap();
+    writes.extend(merge.writes);
+
+    let v4 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert_eq!(v4.record_count(), 2);
+    writes.append(&mut v4.revoke_blob(copy, fetch)?);
+    let v5 = DBView::load(k, writes.iter().map(write_to_read))?;
+    assert!(v5.blob_reader(copy, fetch).is_err());
+    assert_eq!(read(&v5, kept)?, theirs);
+
+    Ok(())
+}
+
+#[test]
+fn test_policy() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let (signing, sealed) = (RecordId::random::<Provider>()?, RecordId::random::<Provider>()?);
+    let data = fresh::data();
+    let mut writes = vec![];
+    let mut w = v0.writer(signing).with_policy(RecordPolicy::only(&[Purpose::Sign]));
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&data, fresh::record_hint())?);
+    let mut w = v0
+        .writer(sealed)
+        .with_policy(RecordPolicy::default().deny(Purpose::Read));
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    assert!(matches!(
+        v1.reader().prepare_read(&signing),
+        Err(vault::Error::PolicyError(_))
+    ));
+    assert_eq!(
+        v1.reader().prepare_use(&signing, Purpose::Sign)?,
+        PreparedRead::CacheHit(data.clone())
+    );
+    assert!(v1.reader().prepare_use(&signing, Purpose::Derive).is_err());
+    assert!(matches!(v1.export(&Key::random()?), Err(vault::Error::PolicyError(_))));
+
+    // data fetched from the storage is checked against the policy as well
+    let storage: HashMap<_, _> = writes.iter().map(|w| (w.id().to_vec(), w.data().to_vec())).collect();
+    let v = DBView::load(
+        k.clone(),
+        writes
+            .iter()
+            .filter(|w| w.kind() == Kind::Transaction)
+            .map(write_to_read),
+    )?;
+    let req = match v.reader().prepare_use(&signing, Purpose::Sign)? {
+        PreparedRead::CacheMiss(req) => req,
+        _ => panic!("unexpected result"),
+    };
+    let res = req.result(storage[req.id()].clone());
+    assert!(matches!(
+        v.reader().read(res.clone()),
+        Err(vault::Error::PolicyError(_))
+    ));
+    assert_eq!(v.reader().read_use(res, Purpose::Sign)?, data);
+
+    // later writes can't loosen the policy
+    writes.append(
+        &mut v1
+            .writer(signing)
+            .with_policy(RecordPolicy::default())
+            .write(&fresh::data(), fresh::record_hint())?,
+    );
+    let v2 = DBView::load(k.clone(), writes.iter().map(write_to_read))?;
+    let policy = v2.metadata(&signing).map(|m| m.policy).unwrap();
+    assert!(policy.allows(Purpose::Sign));
+    assert!(!policy.allows(Purpose::Read));
+    assert!(!policy.allows(Purpose::Export));
+
+    // the policy travels with exported records
+    writes.push(v2.writer(signing).revoke()?);
+    let v3 = DBView::load(k, writes.iter().map(write_to_read))?;
+    let xk: Key<Provider> = Key::random()?;
+    let export = v3.export(&xk)?;
+
+    let k: Key<Provider> = Key::random()?;
+    let v4 = DBView::load(k.clone(), empty::<ReadResult>())?;
+    let report = v4.import(&export, &xk, CollisionPolicy::Fail)?;
+    let v5 = DBView::load(k, report.writes.iter().map(write_to_read))?;
+    assert!(v5.reader().prepare_read(&sealed).is_err());
+    assert_eq!(
+        v5.metadata(&sealed).map(|m| m.policy),
+        v3.metadata(&sealed).map(|m| m.policy)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_hooks() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let hooks = Hooks::default();
+    let events = hooks.channel();
+    let revoked = Arc::new(Mutex::new(vec![]));
+    let r = revoked.clone();
+    hooks.subscribe(move |e| {
+        if let Event::Revoked(id) = e {
+            r.lock().unwrap().push(*id);
+        }
+    });
+
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?.with_hooks(hooks.clone());
+    let id = RecordId::random::<Provider>()?;
+    let mut writes = vec![];
+    let mut w = v0.writer(id);
+    writes.push(w.truncate()?);
+    writes.append(&mut w.write(&fresh::data(), fresh::record_hint())?);
+
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?.with_hooks(hooks.clone());
+    v1.reader().prepare_read(&id)?;
+    writes.push(v1.writer(id).revoke()?);
+
+    let v2 = DBView::load(k.clone(), writes.iter().map(write_to_read))?.with_hooks(hooks.clone());
+    v2.gc();
+
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![
+            Event::Truncated(id),
+            Event::Written(id),
+            Event::Read {
+                record: id,
+                purpose: Purpose::Read
+            },
+            Event::Revoked(id),
+            Event::Collected { transactions: 3 },
+        ]
+    );
+    assert_eq!(*revoked.lock().unwrap(), vec![id]);
+
+    // the events of a failed import aren't emitted
+    let export = v1.export(&k)?;
+    let v3 = DBView::load(k.clone(), empty::<ReadResult>())?
+        .with_hooks(hooks.clone())
+        .with_quota(Quota {
+            max_records: Some(0),
+            max_bytes: None,
+        });
+    assert!(v3.import(&export, &k, CollisionPolicy::Fail).is_err());
+    let v3 = v3.with_quota(Quota::default());
+    v3.import(&export, &k, CollisionPolicy::Fail)?;
+
+    // the chunks of large blobs don't emit events
+    let blob = RecordId::random::<Provider>()?;
+    let mut w = v3.blob_writer(blob, fresh::record_hint(), |_| Ok(()));
+    io::copy(&mut vec![0; 2 * CHUNK_SIZE].as_slice(), &mut w).unwrap();
+    w.finish()?;
+
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        vec![
+            Event::Read {
+                record: id,
+                purpose: Purpose::Export
+            },
+            Event::Truncated(id),
+            Event::Written(id),
+            Event::Truncated(blob),
+            Event::Written(blob),
+        ]
+    );
+
+    // dropping the receiver unsubscribes the channel
+    drop(events);
+    v2.gc();
+
+    Ok(())
+}
+
+#[test]
+fn test_read_cache() -> Result<()> {
+    let k: Key<Provider> = Key::random()?;
+    let v0 = DBView::load(k.clone(), empty::<ReadResult>())?;
+
+    let mut writes = vec![];
+    let mut ids = vec![];
+    for i in 0..3 {
+        let id = RecordId::random::<Provider>()?;
+        let mut w = v0.writer(id);
+        writes.push(w.truncate()?);
+        writes.append(&mut w.write(&[i; 16], fresh::record_hint())?);
+        ids.push(id);
+    }
+
+    // room for two records
+    let cache = ReadCache::new(32);
+    let v1 = DBView::load(k.clone(), writes.iter().map(write_to_read))?.with_read_cache(cache.clone());
+    let read = |v: &DBView<Provider>, i: usize| v.reader().prepare_read(&ids[i]);
+
+    assert_eq!(read(&v1, 0)?, PreparedRead::CacheHit(vec![0; 16]));
+    assert_eq!(read(&v1, 0)?, PreparedRead::CacheHit(vec![0; 16]));
+    read(&v1, 1)?;
+    read(&v1, 2)?;
+    read(&v1, 0)?;
+    assert_eq!(
+        cache.stats(),
+        ReadCacheStats {
+            hits: 1,
+            misses: 4,
+            evictions: 2,
+            entries: 2,
+            bytes: 32,
+        }
+    );
+
+    // the cache is shared with reloaded views, which may not have loaded the data at all
+    let v2 = DBView::load(
+        k.clone(),
+        writes
+            .iter()
+            .filter(|w| w.kind() == Kind::Transaction)
+            .map(write_to_read),
+    )?
+    .with_read_cache(cache.clone());
+    assert_eq!(read(&v2, 2)?, PreparedRead::CacheHit(vec![2; 16]));
+    assert!(matches!(read(&v2, 1)?, PreparedRead::CacheMiss(_)));
+    assert_eq!(cache.stats().hits, 2);
+
+    // revoking a record drops its data
+    v2.writer(ids[2]).revoke()?;
+    assert_eq!(cache.stats().entries, 1);
+
+    // so does attaching the cache to a view which no longer refers to the data
+    writes.append(&mut v1.writer(ids[0]).write(&[3; 16], fresh::record_hint())?);
+    DBView::load(k, writes.iter().map(write_to_read))?.with_read_cache(cache.clone());
+    assert_eq!(cache.stats().entries, 0);
+
+    cache.clear();
+    assert_eq!(cache.stats().bytes, 0);
+
+    Ok(())
+}
@@ -9,7 +9,7 @@ use engine::{
     },
 };
 
-use engine::vault::{BoxProvider, Key};
+use engine::vault::{BoxProvider, CipherSuite, Key};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -32,6 +32,11 @@ impl BoxProvider for Provider {
         Self::NONCE_LEN + Self::TAG_LEN
     }
 
+    // declare the cipher suite the box seals with.
+    fn box_cipher_suite() -> CipherSuite {
+        CipherSuite::XChaCha20Poly1305
+    }
+
     // seal a box with the key.  Append the nonce to the data after encryption
     fn box_seal(key: &Key<Self>, ad: &[u8], data: &[u8]) -> engine::vault::Result<Vec<u8>> {
         let mut boxx = vec![0; data.len() + Self::box_overhead()];